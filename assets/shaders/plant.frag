@@ -16,15 +16,17 @@ layout(set = 0, binding = 1) uniform Sun {
     vec3 SunPos;
 };
 
-layout(set = 2, binding = 1) uniform PlantMaterial_growth {
-    float Growth;
-};
+layout(set = 2, binding = 0) uniform texture2D PlantMaterial_texture;
+layout(set = 2, binding = 1) uniform sampler PlantMaterial_texture_sampler;
+
+layout(set = 2, binding = 2) uniform texture2D PlantMaterial_leaf_front;
+layout(set = 2, binding = 3) uniform sampler PlantMaterial_leaf_front_sampler;
 
-layout(set = 2, binding = 2) uniform texture2D PlantMaterial_texture;
-layout(set = 2, binding = 3) uniform sampler PlantMaterial_texture_sampler;
+layout(set = 2, binding = 4) uniform texture2D PlantMaterial_blossom;
+layout(set = 2, binding = 5) uniform sampler PlantMaterial_blossom_sampler;
 
-layout(set = 2, binding = 4) uniform texture2D PlantMaterial_leaf_front;
-layout(set = 2, binding = 5) uniform sampler PlantMaterial_leaf_front_sampler;
+layout(set = 2, binding = 6) uniform texture2D PlantMaterial_fruit;
+layout(set = 2, binding = 7) uniform sampler PlantMaterial_fruit_sampler;
 
 layout(set = 3, binding = 0) uniform texture2D ShadowMapTexture;
 layout(set = 3, binding = 1) uniform sampler ShadowMapSampler;
@@ -40,12 +42,6 @@ float calculateShadow(in vec2 uv, in float dist, in float bias) {
 }
 
 void main() {
-    float dither = length(sin(v_ModelPos * 50.0)) - (Growth - v_Sway) * 4.0 + 0.5;
-
-    if (Growth < v_Sway || dither > 0.9) {
-        discard;
-    }
-
     vec3 s = v_ShadowCoord.xyz / v_ShadowCoord.w;
     s.y *= -1.0;
 
@@ -104,6 +100,24 @@ void main() {
         color *= tex.rgb;
     }
 
+    // blossom
+    if (v_Material == 2) {
+        vec4 tex = texture(sampler2D(PlantMaterial_blossom, PlantMaterial_blossom_sampler), v_Uv);
+
+        if (tex.a < 0.5) {
+            discard;
+        }
+
+        color *= tex.rgb;
+    }
+
+    // fruit, cone and seed pod, told apart by their color
+    if (v_Material >= 3) {
+        vec4 tex = texture(sampler2D(PlantMaterial_fruit, PlantMaterial_fruit_sampler), v_Uv);
+
+        color *= tex.rgb;
+    }
+
     color = color * light;
 
     o_Target = vec4(color, 1.0);
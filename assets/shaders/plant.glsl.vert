@@ -6,6 +6,9 @@ layout(location = 2) in vec4 Vertex_Color;
 layout(location = 3) in float Plant_Sway;
 layout(location = 4) in vec2 Vertex_Uv;
 layout(location = 5) in uint Plant_Material;
+layout(location = 6) in vec3 Plant_Pivot;
+layout(location = 7) in float Plant_Level;
+layout(location = 8) in float Plant_Phase;
 
 layout(location = 0) out vec3 v_Normal;
 layout(location = 1) out vec3 v_Color;
@@ -25,21 +28,28 @@ layout(set = 0, binding = 1) uniform Sun {
     vec3 SunPos;
 };
 
+layout(set = 0, binding = 2) uniform Wind {
+    vec2 WindDirection;
+    float WindStrength;
+    float WindTime;
+    float WindGust;
+    float WindGustScale;
+    float WindTurbulence;
+};
+
 layout(set = 1, binding = 0) uniform Transform {
     mat4 Model;
 };
 
-layout(set = 2, binding = 0) uniform PlantMaterial_time {
-    float Time;
-};
+
+#include "wind.glsl"
 
 void main() {
     vec3 model_position = Vertex_Position;
 
     vec3 world_position = (Model * vec4(model_position, 1.0)).xyz;
-    float sway = Plant_Sway;
-    sway = pow(sway, 1.3);
-    world_position.xz += sin(Time) * sway * 0.002;
+    vec3 world_pivot = (Model * vec4(Plant_Pivot, 1.0)).xyz;
+    world_position += wind_offset(world_position, world_pivot, max(model_position.y, 0.0));
 
     vec4 normal = Model * vec4(Vertex_Normal, 0.0);
     v_Normal = normalize(normal.xyz);
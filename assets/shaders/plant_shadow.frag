@@ -12,20 +12,13 @@ layout(set = 0, binding = 0) uniform Sun {
 	vec3 Pos;
 };
 
-layout(set = 2, binding = 1) uniform PlantMaterial_growth {
-    float Growth;
-};
+layout(set = 2, binding = 0) uniform texture2D PlantMaterial_leaf_front;
+layout(set = 2, binding = 1) uniform sampler PlantMaterial_leaf_front_sampler;
 
-layout(set = 2, binding = 2) uniform texture2D PlantMaterial_leaf_front;
-layout(set = 2, binding = 3) uniform sampler PlantMaterial_leaf_front_sampler;
+layout(set = 2, binding = 2) uniform texture2D PlantMaterial_blossom;
+layout(set = 2, binding = 3) uniform sampler PlantMaterial_blossom_sampler;
 
 void main() {
-    float dither = length(sin(v_ModelPos * 50.0)) - (Growth - v_Sway) * 4.0 + 0.5;
-
-    if (Growth < v_Sway || dither > 0.9) {
-        discard;
-    }
-
     if (v_Material == 1) {
         vec4 tex = texture(sampler2D(PlantMaterial_leaf_front, PlantMaterial_leaf_front_sampler), v_Uv / 1.0);
 
@@ -34,6 +27,14 @@ void main() {
         }
     }
 
+    if (v_Material == 2) {
+        vec4 tex = texture(sampler2D(PlantMaterial_blossom, PlantMaterial_blossom_sampler), v_Uv);
+
+        if (tex.a < 0.5) {
+            discard;
+        }
+    }
+
 	float far = ViewProj[3][3] - ViewProj[2][3];
 	gl_FragDepth = length(v_WorldPos - Pos) / 200.0;
 }
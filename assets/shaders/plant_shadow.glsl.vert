@@ -4,6 +4,9 @@ layout(location = 0) in vec3 Vertex_Position;
 layout(location = 1) in float Plant_Sway;
 layout(location = 2) in vec2 Vertex_Uv;
 layout(location = 3) in uint Plant_Material;
+layout(location = 4) in vec3 Plant_Pivot;
+layout(location = 5) in float Plant_Level;
+layout(location = 6) in float Plant_Phase;
 
 layout(location = 0) out vec4 v_Pos;
 layout(location = 1) out vec3 v_ModelPos;
@@ -17,19 +20,26 @@ layout(set = 0, binding = 0) uniform Sun {
     vec3 Pos;
 };
 
+layout(set = 0, binding = 1) uniform Wind {
+    vec2 WindDirection;
+    float WindStrength;
+    float WindTime;
+    float WindGust;
+    float WindGustScale;
+    float WindTurbulence;
+};
+
 layout(set = 1, binding = 0) uniform Transform {
     mat4 Model;
 };
 
-layout(set = 2, binding = 0) uniform PlantMaterial_time {
-    float Time;
-};
+
+#include "wind.glsl"
 
 void main() {
     vec3 world_pos = (Model * vec4(Vertex_Position, 1.0)).xyz;
-    float sway = Plant_Sway;
-    sway = pow(sway, 1.3);
-    world_pos.xz += sin(Time) * sway * 0.002;
+    vec3 world_pivot = (Model * vec4(Plant_Pivot, 1.0)).xyz;
+    world_pos += wind_offset(world_pos, world_pivot, max(Vertex_Position.y, 0.0));
 
     vec4 p = ViewProj * vec4(world_pos, 1.0);
     gl_Position = p;
@@ -0,0 +1,31 @@
+// How far the wind pushes a vertex, shared by the plant and plant shadow vertex
+// shaders. Expects the `Wind` uniform and the `Plant_Level`, `Plant_Phase` and
+// `Plant_Material` attributes to be declared by the shader including it.
+
+vec3 wind_offset(vec3 world_position, vec3 world_pivot, float height) {
+    vec3 direction = vec3(WindDirection.x, 0.0, WindDirection.y);
+    vec3 side = vec3(-direction.z, 0.0, direction.x);
+
+    // gust fronts roll through the world along the wind
+    float front = dot(world_position.xz, WindDirection) / WindGustScale - WindTime * 0.3;
+    float gust = WindGust * max(sin(front * 6.2832) * sin(front * 2.7 + 1.3), 0.0);
+    float wind = WindStrength * (1.0 + gust);
+
+    // the whole plant bends away from the wind, more the higher up, roots below the
+    // ground are held still by it
+    vec3 offset = direction * wind * height * height * 0.008;
+
+    // branches swing around where they sprout, each at its own pace
+    float reach = length(world_position - world_pivot);
+    float swing = sin(WindTime * (1.3 + Plant_Level * 0.6) + Plant_Phase) * (0.4 + WindTurbulence);
+    offset += (direction + side * cos(Plant_Phase)) * swing * wind * reach * 0.02 * min(Plant_Level, 3.0);
+
+    // leaves flutter quickly and on their own
+    if (Plant_Material == 1) {
+        float t = WindTime * 9.0 + Plant_Phase;
+
+        offset += vec3(sin(t), sin(t * 1.3 + 0.7), cos(t * 1.1)) * WindTurbulence * (0.2 + wind) * 0.03;
+    }
+
+    return offset;
+}
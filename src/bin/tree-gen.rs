@@ -0,0 +1,299 @@
+//! Generates plants without opening a window, for batch runs and comparing species.
+//!
+//! ```text
+//! tree-gen <plant.gno|plant.lsys> [--seed <n>] [--position <x,z>] [--age <seconds>] [--season <0..1>] [--light <x,y,z>] [--lod <level>] [--occlusion <rays>] [--out <dir>] [--format <glb,gltf,obj>]
+//! ```
+//!
+//! Writes `<name>_<seed>.<format>` for every format and a `<name>_<seed>.json` report.
+//!
+//! With `--position`, `--seed` is the world seed and the plant seed is derived the same
+//! way the game derives it for a plant standing at `x, z`. Plants are fully grown unless
+//! an `--age` is given, and carry every organ unless a `--season` is given. Tropisms
+//! turn toward light from straight above unless a `--light` direction is given. Ambient
+//! occlusion is baked with `--occlusion` rays per vertex, 8 unless given, 0 turns it off.
+
+use anyhow::{anyhow, bail, Context};
+use bevy::math::Vec3;
+use rand::prelude::*;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+use tree::{
+    export,
+    inheritance::{ExtendsChain, GenomePatch},
+    lsystem::LSystem,
+    metrics::PlantMetrics,
+    occlusion::OcclusionBake,
+    plant::{Genome, PlantGenerator, PlantSeed, WorldSeed},
+    skeleton::*,
+};
+
+const USAGE: &str = "usage: tree-gen <plant.gno|plant.lsys> [--seed <n>] [--position <x,z>] \
+                     [--age <seconds>] [--season <0..1>] [--light <x,y,z>] [--lod <level>] \
+                     [--occlusion <rays>] [--out <dir>] [--format <glb,gltf,obj>]";
+
+struct Args {
+    input: PathBuf,
+    seed: Option<u64>,
+    position: Option<[f32; 2]>,
+    age: Option<f32>,
+    season: Option<f32>,
+    light: Option<[f32; 3]>,
+    lod: usize,
+    occlusion: Option<OcclusionBake>,
+    out: PathBuf,
+    formats: Vec<String>,
+}
+
+impl Args {
+    fn parse() -> anyhow::Result<Self> {
+        let mut input = None;
+        let mut seed = None;
+        let mut position = None;
+        let mut age = None;
+        let mut season = None;
+        let mut light = None;
+        let mut lod = 0;
+        let mut occlusion = Some(OcclusionBake::default());
+        let mut out = PathBuf::from(".");
+        let mut formats = Vec::new();
+
+        let mut args = std::env::args().skip(1);
+
+        while let Some(arg) = args.next() {
+            let mut value = || {
+                args.next()
+                    .ok_or_else(|| anyhow!("'{}' needs a value", arg))
+            };
+
+            match arg.as_str() {
+                "--seed" => seed = Some(value()?.parse().context("invalid --seed")?),
+                "--position" => position = Some(parse_position(&value()?)?),
+                "--age" => age = Some(value()?.parse().context("invalid --age")?),
+                "--season" => season = Some(value()?.parse().context("invalid --season")?),
+                "--light" => light = Some(parse_light(&value()?)?),
+                "--lod" => lod = value()?.parse().context("invalid --lod")?,
+                "--occlusion" => {
+                    occlusion = match value()?.parse().context("invalid --occlusion")? {
+                        0 => None,
+                        rays => Some(OcclusionBake {
+                            rays,
+                            ..Default::default()
+                        }),
+                    }
+                }
+                "--out" => out = PathBuf::from(value()?),
+                "--format" => formats.extend(value()?.split(',').map(str::to_lowercase)),
+                "-h" | "--help" => {
+                    println!("{}", USAGE);
+                    std::process::exit(0);
+                }
+                _ if arg.starts_with('-') => bail!("unknown option '{}'\n{}", arg, USAGE),
+                _ if input.is_none() => input = Some(PathBuf::from(arg)),
+                _ => bail!("unexpected argument '{}'\n{}", arg, USAGE),
+            }
+        }
+
+        if formats.is_empty() {
+            formats.push(String::from("glb"));
+        }
+
+        Ok(Self {
+            input: input.ok_or_else(|| anyhow!("{}", USAGE))?,
+            seed,
+            position,
+            age,
+            season,
+            light,
+            lod,
+            occlusion,
+            out,
+            formats,
+        })
+    }
+}
+
+fn parse_position(value: &str) -> anyhow::Result<[f32; 2]> {
+    let mut coordinates = value.split(',').map(|c| c.trim().parse::<f32>());
+
+    match (coordinates.next(), coordinates.next(), coordinates.next()) {
+        (Some(Ok(x)), Some(Ok(z)), None) => Ok([x, z]),
+        _ => bail!("invalid --position '{}', expected <x,z>", value),
+    }
+}
+
+fn parse_light(value: &str) -> anyhow::Result<[f32; 3]> {
+    let coordinates = value
+        .split(',')
+        .map(|c| c.trim().parse::<f32>())
+        .collect::<Result<Vec<_>, _>>();
+
+    match coordinates.as_deref() {
+        Ok(&[x, y, z]) if Vec3::new(x, y, z).length() > 0.0 => Ok([x, y, z]),
+        _ => bail!("invalid --light '{}', expected a direction <x,y,z>", value),
+    }
+}
+
+#[derive(Serialize)]
+struct Bounds {
+    min: [f32; 3],
+    max: [f32; 3],
+}
+
+#[derive(Serialize)]
+struct Report {
+    source: String,
+    seed: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    world_seed: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    position: Option<[f32; 2]>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    age: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    season: Option<f32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    light: Option<[f32; 3]>,
+    /// The level of detail that was exported, and which `metrics` and `bounds` describe.
+    lod: usize,
+    bounds: Bounds,
+    #[serde(flatten)]
+    metrics: PlantMetrics,
+    files: Vec<String>,
+}
+
+/// Seeds the generator, returning the plant seed and the world seed it was derived
+/// from, if any.
+fn generate<G: PlantGenerator>(generator: G, args: &Args) -> (u64, Option<u64>, PlantSkeleton) {
+    let (seed, world_seed) = match args.position {
+        Some([x, z]) => {
+            let world = WorldSeed(args.seed.unwrap_or_else(|| thread_rng().gen()));
+            let plant = PlantSeed::from_position(world, Vec3::new(x, 0.0, z));
+
+            (generator.instance_seed(None, plant), Some(world.0))
+        }
+        None => (
+            generator.instance_seed(args.seed.map(PlantSeed), PlantSeed(thread_rng().gen())),
+            None,
+        ),
+    };
+
+    let growth = generator.growth();
+    let age = args.age.unwrap_or(growth.max_age);
+    let light = args.light.map_or(Vec3::Y, Vec3::from);
+    let mut skeleton = generator.generate_skeleton(seed, light);
+
+    if let Some(season) = args.season {
+        skeleton = skeleton.in_season(season);
+    }
+
+    (seed, world_seed, skeleton.grown(age, &growth))
+}
+
+/// Loads a genome along with every genome it `extends`. Those are asset paths, so
+/// they're looked up relative to each directory above `path` until one exists.
+fn load_genome(path: &Path, bytes: &[u8]) -> anyhow::Result<Genome> {
+    let mut chain = ExtendsChain::default();
+    chain.visit(path)?;
+
+    let mut genome = GenomePatch::from_bytes(bytes)?;
+
+    while let Some(extends) = genome.extends.clone() {
+        let parent = path
+            .ancestors()
+            .skip(1)
+            .map(|directory| directory.join(&extends))
+            .find(|parent| parent.is_file())
+            .ok_or_else(|| anyhow!("can't find '{}' it extends", extends))?;
+
+        chain.visit(Path::new(&extends))?;
+
+        let bytes = std::fs::read(&parent).with_context(|| format!("'{}'", parent.display()))?;
+        let patch = GenomePatch::from_bytes(&bytes).with_context(|| format!("'{}'", extends))?;
+
+        genome = genome.over(patch);
+    }
+
+    Ok(genome.into_genome()?)
+}
+
+fn load(path: &Path, args: &Args) -> anyhow::Result<(u64, Option<u64>, PlantSkeleton)> {
+    let bytes = std::fs::read(path)?;
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "gno" => Ok(generate(load_genome(path, &bytes)?, args)),
+        "lsys" => {
+            let lsystem = ron::de::from_bytes::<LSystem>(&bytes)?;
+            lsystem.validate()?;
+
+            Ok(generate(lsystem, args))
+        }
+        _ => bail!("expected a .gno or .lsys file"),
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    let args = Args::parse()?;
+
+    let (seed, world_seed, skeleton) =
+        load(&args.input, &args).with_context(|| format!("'{}'", args.input.display()))?;
+    let skeleton = skeleton.lod(args.lod);
+    let (mesh, metrics) = skeleton.generate_mesh(args.occlusion.as_ref());
+    let data = export::PlantMeshData::from_mesh(&mesh)?;
+
+    let (min, max) =
+        data.positions
+            .iter()
+            .fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), p| {
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+
+                (min, max)
+            });
+
+    let name = args
+        .input
+        .file_stem()
+        .map(|stem| stem.to_string_lossy().into_owned())
+        .unwrap_or_else(|| String::from("plant"));
+    // joined by hand, `with_extension` would cut dotted names like `oak.v2` short
+    let stem = format!("{}_{}", name, seed);
+
+    std::fs::create_dir_all(&args.out).with_context(|| format!("'{}'", args.out.display()))?;
+
+    let mut files = Vec::new();
+
+    for format in &args.formats {
+        let path = args.out.join(format!("{}.{}", stem, format));
+
+        export::export_mesh(&mesh, &path).with_context(|| format!("'{}'", path.display()))?;
+        files.push(path.to_string_lossy().into_owned());
+    }
+
+    let report = Report {
+        source: args.input.to_string_lossy().into_owned(),
+        seed,
+        world_seed,
+        position: args.position,
+        age: args.age,
+        season: args.season,
+        light: args.light,
+        lod: args.lod,
+        bounds: Bounds { min, max },
+        metrics,
+        files,
+    };
+
+    let report_path = args.out.join(format!("{}.json", stem));
+    std::fs::write(&report_path, serde_json::to_vec_pretty(&report)?)
+        .with_context(|| format!("'{}'", report_path.display()))?;
+
+    println!("{}", serde_json::to_string_pretty(&report)?);
+
+    Ok(())
+}
@@ -0,0 +1,342 @@
+use crate::envelope::*;
+use crate::phyllotaxis::LeafNodes;
+use crate::plant::*;
+use crate::skeleton::*;
+use bevy::{prelude::*, utils::HashMap};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Grows the branch graph towards attraction points scattered in a crown envelope,
+/// instead of splitting branches recursively.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Colonization {
+    pub envelope: CrownEnvelope,
+    pub attraction_points: usize,
+    pub trunk_height: f32,
+    pub step: f32,
+    pub influence_radius: f32,
+    pub kill_radius: f32,
+    pub max_iterations: usize,
+}
+
+struct Node {
+    position: Vec3,
+    parent: Option<usize>,
+    children: Vec<usize>,
+}
+
+struct NodeGrid {
+    cell: f32,
+    cells: HashMap<(i32, i32, i32), Vec<usize>>,
+}
+
+impl NodeGrid {
+    fn key(&self, point: Vec3) -> (i32, i32, i32) {
+        let p = (point / self.cell).floor();
+
+        (p.x as i32, p.y as i32, p.z as i32)
+    }
+
+    fn insert(&mut self, index: usize, point: Vec3) {
+        let key = self.key(point);
+
+        self.cells.entry(key).or_default().push(index);
+    }
+
+    fn nearest(&self, nodes: &[Node], point: Vec3, radius: f32) -> Option<usize> {
+        let (x, y, z) = self.key(point);
+        let reach = (radius / self.cell).ceil() as i32;
+
+        let mut nearest = None;
+        let mut nearest_distance = radius * radius;
+
+        for dx in -reach..=reach {
+            for dy in -reach..=reach {
+                for dz in -reach..=reach {
+                    let cell = match self.cells.get(&(x + dx, y + dy, z + dz)) {
+                        Some(cell) => cell,
+                        None => continue,
+                    };
+
+                    for &index in cell {
+                        let distance = nodes[index].position.distance_squared(point);
+
+                        if distance <= nearest_distance {
+                            nearest = Some(index);
+                            nearest_distance = distance;
+                        }
+                    }
+                }
+            }
+        }
+
+        nearest
+    }
+}
+
+fn add_node(nodes: &mut Vec<Node>, grid: &mut NodeGrid, parent: usize, position: Vec3) -> usize {
+    let index = nodes.len();
+
+    nodes.push(Node {
+        position,
+        parent: Some(parent),
+        children: Vec::new(),
+    });
+    nodes[parent].children.push(index);
+    grid.insert(index, position);
+
+    index
+}
+
+impl Colonization {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        self.envelope.validate()?;
+
+        check_count("colonization.attraction_points", self.attraction_points, 1)?;
+        check_non_negative("colonization.trunk_height", self.trunk_height)?;
+        check_positive("colonization.step", self.step)?;
+        check_positive("colonization.influence_radius", self.influence_radius)?;
+        check_positive("colonization.kill_radius", self.kill_radius)?;
+
+        // a shape too thin to place points in is rejected here rather than when
+        // a plant grows
+        self.envelope
+            .sample(self.attraction_points, &mut SmallRng::seed_from_u64(0))?;
+
+        Ok(())
+    }
+
+    /// Grows the nodes toward the attraction points, turned toward the
+    /// [`Tropism::pull`](crate::tropism::Tropism::pull) `tropism` as they go.
+    fn grow(&self, rng: &mut impl Rng, tropism: Vec3) -> Vec<Node> {
+        // the envelope has been sampled once by `validate`, so at worst an unlucky
+        // seed grows a bare trunk
+        let mut points = self
+            .envelope
+            .sample(self.attraction_points, rng)
+            .unwrap_or_default();
+
+        let mut nodes = vec![Node {
+            position: Vec3::ZERO,
+            parent: None,
+            children: Vec::new(),
+        }];
+        let mut grid = NodeGrid {
+            cell: self.influence_radius,
+            cells: HashMap::default(),
+        };
+        grid.insert(0, Vec3::ZERO);
+
+        let mut trunk = Some(0);
+
+        for _ in 0..self.max_iterations {
+            if points.is_empty() {
+                break;
+            }
+
+            let mut pull = vec![Vec3::ZERO; nodes.len()];
+
+            for point in &points {
+                if let Some(index) = grid.nearest(&nodes, *point, self.influence_radius) {
+                    pull[index] += (*point - nodes[index].position).normalize_or_zero();
+                }
+            }
+
+            let active = pull
+                .iter()
+                .enumerate()
+                .filter(|(_, pull)| **pull != Vec3::ZERO)
+                .map(|(i, pull)| (i, *pull))
+                .collect::<Vec<_>>();
+
+            // the trunk grows straight up until it reaches the crown
+            if let Some(tip) = trunk {
+                if active.is_empty() || nodes[tip].position.y < self.trunk_height {
+                    let position = nodes[tip].position + Vec3::Y * self.step;
+
+                    trunk = Some(add_node(&mut nodes, &mut grid, tip, position));
+
+                    continue;
+                }
+
+                trunk = None;
+            }
+
+            if active.is_empty() {
+                break;
+            }
+
+            for (index, pull) in active {
+                let node = &nodes[index];
+
+                let previous = match node.parent {
+                    Some(parent) => (node.position - nodes[parent].position).normalize_or_zero(),
+                    None => Vec3::Y,
+                };
+
+                let mut direction = pull.normalize_or_zero();
+
+                if direction.length_squared() < 0.5 {
+                    direction = previous;
+                }
+
+                if tropism != Vec3::ZERO {
+                    direction = (direction + tropism * self.step).normalize_or_zero();
+                }
+
+                let position = node.position + direction * self.step;
+
+                // points pulling equally from both sides would grow the same twig every iteration
+                let duplicate = node.children.iter().any(|&child| {
+                    nodes[child].position.distance_squared(position) < self.step * self.step * 0.01
+                });
+
+                if !duplicate {
+                    add_node(&mut nodes, &mut grid, index, position);
+                }
+            }
+
+            points.retain(|point| grid.nearest(&nodes, *point, self.kill_radius).is_none());
+        }
+
+        nodes
+    }
+
+    pub fn generate_skeleton(
+        &self,
+        genome: &Genome,
+        rng: &mut rand::rngs::SmallRng,
+        pull: Vec3,
+    ) -> PlantSkeleton {
+        let nodes = self.grow(rng, pull);
+
+        // pipe model, every tip carries the same cross section
+        let mut tips = vec![0usize; nodes.len()];
+
+        for i in (0..nodes.len()).rev() {
+            if nodes[i].children.is_empty() {
+                tips[i] = 1;
+            }
+
+            if let Some(parent) = nodes[i].parent {
+                tips[parent] += tips[i];
+            }
+        }
+
+        let radius = |i: usize| genome.starting_radius * (tips[i] as f32 / tips[0] as f32).sqrt();
+
+        let mut distance = vec![0.0; nodes.len()];
+
+        for i in 1..nodes.len() {
+            let parent = nodes[i].parent.unwrap();
+
+            distance[i] = distance[parent] + nodes[i].position.distance(nodes[parent].position);
+        }
+
+        let rotation = |i: usize| {
+            let node = &nodes[i];
+
+            let incoming = match node.parent {
+                Some(parent) => (node.position - nodes[parent].position).normalize_or_zero(),
+                None => Vec3::ZERO,
+            };
+            let outgoing = match node.children.first() {
+                Some(&child) => (nodes[child].position - node.position).normalize_or_zero(),
+                None => Vec3::ZERO,
+            };
+
+            let direction = (incoming + outgoing).normalize_or_zero();
+
+            if direction == Vec3::ZERO {
+                Quat::IDENTITY
+            } else {
+                Quat::from_rotation_arc(Vec3::Y, direction)
+            }
+        };
+
+        let frame = |i: usize| SegmentFrame {
+            position: nodes[i].position,
+            rotation: rotation(i),
+            radius: radius(i),
+            sway: distance[i],
+        };
+
+        let mut skeleton = PlantSkeleton::default();
+
+        // (junction node, first node of the branch, parent branch, depth)
+        let mut stack = nodes[0]
+            .children
+            .iter()
+            .rev()
+            .map(|&child| (0, child, None, 0))
+            .collect::<Vec<_>>();
+
+        while let Some((junction, first, parent, depth)) = stack.pop() {
+            let mut chain = vec![junction, first];
+
+            while let [child] = nodes[*chain.last().unwrap()].children[..] {
+                chain.push(child);
+            }
+
+            let frames = chain.iter().map(|&i| frame(i)).collect::<Vec<_>>();
+            let mut leaves = Vec::new();
+
+            let radial_segments = if genome.levels.is_empty() {
+                genome.unlevelled_radial_segments(depth, 1)
+            } else {
+                genome.level(depth, frames[0].position.y).radial_segments
+            };
+
+            if depth >= genome.leaf_start {
+                let mut nodes = LeafNodes::new(
+                    &genome.phyllotaxis,
+                    genome.segments_per_branch,
+                    radial_segments,
+                    0,
+                );
+
+                for segment in frames.windows(2) {
+                    nodes.place(
+                        rng,
+                        &genome.level(depth, segment[1].position.y),
+                        &segment[0],
+                        &segment[1],
+                        &mut leaves,
+                    );
+                }
+            }
+
+            let last = *chain.last().unwrap();
+            let tip = nodes[last].children.is_empty();
+            let organs = genome.place_organs(rng, depth, &frames, radial_segments, tip);
+
+            let start = frames[0];
+            let end = frames[frames.len() - 1];
+
+            let index = skeleton.add_branch(SkeletonBranch {
+                parent,
+                parent_frame: parent.map_or(0, |p: usize| skeleton.branches[p].frames.len() - 1),
+                children: Vec::new(),
+                split: depth,
+                start: start.position,
+                direction: (frames[1].position - start.position).normalize_or_zero(),
+                bend: Vec3::ZERO,
+                start_radius: start.radius,
+                end_radius: end.radius,
+                length: end.sway - start.sway,
+                radial_segments,
+                sway: start.sway,
+                frames,
+                leaves,
+                organs,
+                root: false,
+            });
+
+            for &child in nodes[last].children.iter().rev() {
+                stack.push((last, child, Some(index), depth + 1));
+            }
+        }
+
+        skeleton
+    }
+}
@@ -0,0 +1,342 @@
+use crate::plant::*;
+use serde::{
+    de::{self, value::MapAccessDeserializer, Visitor},
+    Deserialize, Deserializer, Serialize,
+};
+
+/// A genome parameter that is either one value for the whole plant or a [`Curve`]
+/// over the split depth or height of the branch it's sampled at.
+///
+/// ```ron
+/// branch_bend: 0.65,
+/// branch_bend: ( along: Height, keys: [(0.0, 1.2), (1.0, 0.2)] ),
+/// ```
+#[derive(Serialize, Clone, Debug, PartialEq)]
+#[serde(untagged)]
+pub enum Param {
+    Constant(f32),
+    Curve(Curve),
+}
+
+/// Keyframes of `(position, value)`, positions go from 0 to 1 along `along` and are
+/// in ascending order. Values are held flat before the first and after the last key.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct Curve {
+    #[serde(default)]
+    pub along: CurveAxis,
+    #[serde(default)]
+    pub interpolation: Interpolation,
+    pub keys: Vec<(f32, f32)>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum CurveAxis {
+    /// The split level, from the trunk at 0 to the last split at 1.
+    #[default]
+    Depth,
+    /// Height above the ground, 1 being the height the plant would reach if every
+    /// branch grew straight up.
+    Height,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum Interpolation {
+    #[default]
+    Linear,
+    /// Eases in and out of every key.
+    Smooth,
+}
+
+/// Where on the plant a [`Param`] is sampled, both normalized to 0..=1.
+#[derive(Clone, Copy, Debug)]
+pub struct CurvePosition {
+    pub depth: f32,
+    pub height: f32,
+}
+
+impl CurvePosition {
+    pub fn new(genome: &Genome, split: usize, height: f32) -> Self {
+        let depth = split as f32 / (genome.max_splits - 1) as f32;
+        let height = height / (genome.branch_length * genome.max_splits as f32);
+
+        Self {
+            depth: depth.clamp(0.0, 1.0),
+            height: if height.is_finite() {
+                height.clamp(0.0, 1.0)
+            } else {
+                0.0
+            },
+        }
+    }
+}
+
+impl Param {
+    pub fn sample(&self, position: CurvePosition) -> f32 {
+        match self {
+            Self::Constant(value) => *value,
+            Self::Curve(curve) => curve.sample(position),
+        }
+    }
+
+    /// Checks every value with `check`, and that a curve has keys in order.
+    pub fn validate(
+        &self,
+        field: &'static str,
+        check: fn(&'static str, f32) -> Result<(), GenomeError>,
+    ) -> Result<(), GenomeError> {
+        match self {
+            Self::Constant(value) => check(field, *value),
+            Self::Curve(curve) => {
+                if curve.keys.is_empty() {
+                    return Err(GenomeError::EmptyCurve { field });
+                }
+
+                let mut last = 0.0;
+
+                for &(position, value) in &curve.keys {
+                    if !position.is_finite() {
+                        return Err(GenomeError::NotFinite { field });
+                    }
+
+                    if !(last..=1.0).contains(&position) {
+                        return Err(GenomeError::OutOfRange {
+                            field,
+                            value: position as f64,
+                            min: last as f64,
+                            max: 1.0,
+                        });
+                    }
+
+                    check(field, value)?;
+                    last = position;
+                }
+
+                Ok(())
+            }
+        }
+    }
+}
+
+// an untagged derive loses track of enum variants like `along: Height` in RON, so
+// numbers and structs are told apart by hand
+impl<'de> Deserialize<'de> for Param {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct ParamVisitor;
+
+        impl<'de> Visitor<'de> for ParamVisitor {
+            type Value = Param;
+
+            fn expecting(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                f.write_str("a number or a curve")
+            }
+
+            fn visit_f64<E: de::Error>(self, value: f64) -> Result<Param, E> {
+                Ok(Param::Constant(value as f32))
+            }
+
+            fn visit_i64<E: de::Error>(self, value: i64) -> Result<Param, E> {
+                Ok(Param::Constant(value as f32))
+            }
+
+            fn visit_u64<E: de::Error>(self, value: u64) -> Result<Param, E> {
+                Ok(Param::Constant(value as f32))
+            }
+
+            fn visit_map<A: de::MapAccess<'de>>(self, map: A) -> Result<Param, A::Error> {
+                Curve::deserialize(MapAccessDeserializer::new(map)).map(Param::Curve)
+            }
+        }
+
+        deserializer.deserialize_any(ParamVisitor)
+    }
+}
+
+impl From<f32> for Param {
+    fn from(value: f32) -> Self {
+        Self::Constant(value)
+    }
+}
+
+impl Curve {
+    pub fn sample(&self, position: CurvePosition) -> f32 {
+        let x = match self.along {
+            CurveAxis::Depth => position.depth,
+            CurveAxis::Height => position.height,
+        };
+
+        let next = self.keys.iter().position(|&(position, _)| position > x);
+
+        match next {
+            Some(0) => self.keys[0].1,
+            Some(i) => {
+                let (x0, y0) = self.keys[i - 1];
+                let (x1, y1) = self.keys[i];
+                let t = (x - x0) / (x1 - x0);
+
+                let t = match self.interpolation {
+                    Interpolation::Linear => t,
+                    Interpolation::Smooth => t * t * (3.0 - 2.0 * t),
+                };
+
+                y0 + (y1 - y0) * t
+            }
+            None => self.keys.last().map_or(0.0, |&(_, value)| value),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn curve(interpolation: Interpolation, keys: &[(f32, f32)]) -> Curve {
+        Curve {
+            along: CurveAxis::Depth,
+            interpolation,
+            keys: keys.to_vec(),
+        }
+    }
+
+    fn at(depth: f32) -> CurvePosition {
+        CurvePosition { depth, height: 0.0 }
+    }
+
+    fn check(field: &'static str, value: f32) -> Result<(), GenomeError> {
+        check_non_negative(field, value)
+    }
+
+    #[test]
+    fn sample_holds_flat_outside_the_keys() {
+        let curve = curve(Interpolation::Linear, &[(0.25, 1.0), (0.75, 3.0)]);
+
+        assert_eq!(curve.sample(at(0.0)), 1.0);
+        assert_eq!(curve.sample(at(0.25)), 1.0);
+        assert_eq!(curve.sample(at(0.75)), 3.0);
+        assert_eq!(curve.sample(at(1.0)), 3.0);
+    }
+
+    #[test]
+    fn sample_interpolates_between_keys() {
+        let linear = curve(Interpolation::Linear, &[(0.0, 1.0), (0.5, 2.0), (1.0, 0.0)]);
+        let smooth = curve(Interpolation::Smooth, &[(0.0, 0.0), (1.0, 1.0)]);
+
+        assert_eq!(linear.sample(at(0.25)), 1.5);
+        assert_eq!(linear.sample(at(0.75)), 1.0);
+        assert_eq!(smooth.sample(at(0.25)), 0.156_25);
+        assert_eq!(smooth.sample(at(0.5)), 0.5);
+    }
+
+    #[test]
+    fn sample_follows_its_axis() {
+        let curve = Curve {
+            along: CurveAxis::Height,
+            ..curve(Interpolation::Linear, &[(0.0, 0.0), (1.0, 1.0)])
+        };
+        let position = CurvePosition {
+            depth: 1.0,
+            height: 0.25,
+        };
+
+        assert_eq!(curve.sample(position), 0.25);
+        assert_eq!(Param::Constant(4.0).sample(position), 4.0);
+    }
+
+    #[test]
+    fn validate_takes_keys_in_order() {
+        let param = Param::Curve(curve(
+            Interpolation::Linear,
+            &[(0.0, 1.0), (0.5, 1.0), (0.5, 2.0)],
+        ));
+
+        assert_eq!(param.validate("bend", check), Ok(()));
+    }
+
+    #[test]
+    fn validate_rejects_bad_keys() {
+        let validate = |keys: &[(f32, f32)]| {
+            Param::Curve(curve(Interpolation::Linear, keys)).validate("bend", check)
+        };
+
+        assert_eq!(
+            validate(&[]),
+            Err(GenomeError::EmptyCurve { field: "bend" })
+        );
+        assert_eq!(
+            validate(&[(0.5, 1.0), (0.25, 1.0)]),
+            Err(GenomeError::OutOfRange {
+                field: "bend",
+                value: 0.25,
+                min: 0.5,
+                max: 1.0,
+            })
+        );
+        assert_eq!(
+            validate(&[(1.5, 1.0)]),
+            Err(GenomeError::OutOfRange {
+                field: "bend",
+                value: 1.5,
+                min: 0.0,
+                max: 1.0,
+            })
+        );
+        assert_eq!(
+            validate(&[(f32::NAN, 1.0)]),
+            Err(GenomeError::NotFinite { field: "bend" })
+        );
+        assert!(validate(&[(0.5, -1.0)]).is_err());
+        assert!(Param::Constant(-1.0).validate("bend", check).is_err());
+    }
+
+    #[test]
+    fn position_is_normalized_and_clamped() {
+        let genome = Genome::test();
+        let height = genome.branch_length * genome.max_splits as f32;
+
+        let position = CurvePosition::new(&genome, 2, height * 0.5);
+        assert_eq!(position.depth, 0.5);
+        assert_eq!(position.height, 0.5);
+
+        let position = CurvePosition::new(&genome, 9, height * 2.0);
+        assert_eq!(position.depth, 1.0);
+        assert_eq!(position.height, 1.0);
+
+        let position = CurvePosition::new(&genome, 0, -1.0);
+        assert_eq!(position.depth, 0.0);
+        assert_eq!(position.height, 0.0);
+
+        let genome = Genome {
+            branch_length: 0.0,
+            ..genome
+        };
+        assert_eq!(CurvePosition::new(&genome, 0, 0.0).height, 0.0);
+    }
+
+    #[test]
+    fn deserializes_numbers_as_constants() {
+        assert_eq!(ron::from_str::<Param>("0.65"), Ok(Param::Constant(0.65)));
+        assert_eq!(ron::from_str::<Param>("2"), Ok(Param::Constant(2.0)));
+        assert_eq!(ron::from_str::<Param>("-1"), Ok(Param::Constant(-1.0)));
+    }
+
+    #[test]
+    fn deserializes_structs_as_curves() {
+        assert_eq!(
+            ron::from_str::<Param>("( along: Height, keys: [(0.0, 1.2), (1.0, 0.2)] )"),
+            Ok(Param::Curve(Curve {
+                along: CurveAxis::Height,
+                interpolation: Interpolation::Linear,
+                keys: vec![(0.0, 1.2), (1.0, 0.2)],
+            }))
+        );
+        assert_eq!(
+            ron::from_str::<Param>("( interpolation: Smooth, keys: [(0.5, 1.0)] )"),
+            Ok(Param::Curve(Curve {
+                along: CurveAxis::Depth,
+                interpolation: Interpolation::Smooth,
+                keys: vec![(0.5, 1.0)],
+            }))
+        );
+        assert!(ron::from_str::<Param>("( along: Height )").is_err());
+        assert!(ron::from_str::<Param>("\"0.5\"").is_err());
+    }
+}
@@ -0,0 +1,494 @@
+use crate::plant::*;
+use crate::skeleton::*;
+use bevy::{prelude::*, utils::HashMap};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// The volume a crown is allowed to fill.
+#[derive(Serialize, Deserialize, Clone)]
+pub enum CrownEnvelope {
+    Ellipsoid {
+        center: Vec3,
+        radii: Vec3,
+    },
+    /// A cone standing on a disc of `radius` at height `base`, with its tip `height` above.
+    Cone {
+        base: f32,
+        height: f32,
+        radius: f32,
+    },
+    /// An upright cylinder of `radius` from height `base` to `height` above it.
+    Column {
+        base: f32,
+        height: f32,
+        radius: f32,
+    },
+    /// A box `size` wide along x and z around the trunk, filled from height `base`
+    /// up to `heights` above it. Rows of heights run along x and go from -z to +z,
+    /// and are blended between.
+    Heightfield {
+        base: f32,
+        size: Vec2,
+        heights: Vec<Vec<f32>>,
+    },
+    Points(Vec<Vec3>),
+}
+
+impl CrownEnvelope {
+    pub fn contains(&self, point: Vec3) -> bool {
+        match self {
+            Self::Ellipsoid { center, radii } => {
+                ((point - *center) / *radii).length_squared() <= 1.0
+            }
+            Self::Cone {
+                base,
+                height,
+                radius,
+            } => {
+                let y = (point.y - base) / height;
+
+                (0.0..=1.0).contains(&y)
+                    && Vec2::new(point.x, point.z).length() <= radius * (1.0 - y)
+            }
+            Self::Column {
+                base,
+                height,
+                radius,
+            } => {
+                (*base..=base + height).contains(&point.y)
+                    && Vec2::new(point.x, point.z).length() <= *radius
+            }
+            Self::Heightfield { base, size, .. } => {
+                point.x.abs() <= size.x / 2.0
+                    && point.z.abs() <= size.y / 2.0
+                    && point.y >= *base
+                    && point.y <= self.top(point)
+            }
+            Self::Points(_) => true,
+        }
+    }
+
+    /// Height of the top of a `Heightfield` above `point`, sampled between the
+    /// nearest four heights.
+    fn top(&self, point: Vec3) -> f32 {
+        let (base, size, heights) = match self {
+            Self::Heightfield {
+                base,
+                size,
+                heights,
+            } => (*base, *size, heights),
+            _ => return f32::INFINITY,
+        };
+
+        let rows = heights.len();
+        let columns = heights[0].len();
+
+        let u = ((point.x / size.x + 0.5) * (columns - 1) as f32).clamp(0.0, (columns - 1) as f32);
+        let v = ((point.z / size.y + 0.5) * (rows - 1) as f32).clamp(0.0, (rows - 1) as f32);
+
+        let (x0, z0) = (u.floor() as usize, v.floor() as usize);
+        let (x1, z1) = ((x0 + 1).min(columns - 1), (z0 + 1).min(rows - 1));
+        let (tx, tz) = (u.fract(), v.fract());
+
+        let near = heights[z0][x0] + (heights[z0][x1] - heights[z0][x0]) * tx;
+        let far = heights[z1][x0] + (heights[z1][x1] - heights[z1][x0]) * tx;
+
+        base + near + (far - near) * tz
+    }
+
+    /// The direction from `point` back into the envelope.
+    pub fn inward(&self, point: Vec3) -> Vec3 {
+        let upright = |base: f32, top: f32| {
+            let vertical = if point.y < base {
+                Vec3::Y
+            } else if point.y > top {
+                -Vec3::Y
+            } else {
+                Vec3::ZERO
+            };
+
+            (Vec3::new(-point.x, 0.0, -point.z).normalize_or_zero() + vertical).normalize_or_zero()
+        };
+
+        match self {
+            Self::Ellipsoid { center, .. } => (*center - point).normalize_or_zero(),
+            Self::Cone { base, height, .. } | Self::Column { base, height, .. } => {
+                upright(*base, base + height)
+            }
+            Self::Heightfield { base, .. } => upright(*base, self.top(point)),
+            Self::Points(points) => {
+                let center =
+                    points.iter().fold(Vec3::ZERO, |sum, p| sum + *p) / points.len() as f32;
+
+                (center - point).normalize_or_zero()
+            }
+        }
+    }
+
+    /// Tries to place a point this many times over before giving up on a shape
+    /// too thin to hold them.
+    const SAMPLE_ATTEMPTS: usize = 1000;
+
+    /// `count` random points inside the envelope, or all of a `Points` envelope.
+    pub fn sample(&self, count: usize, rng: &mut impl Rng) -> Result<Vec<Vec3>, GenomeError> {
+        let (min, max) = match self {
+            Self::Ellipsoid { center, radii } => (*center - *radii, *center + *radii),
+            Self::Cone {
+                base,
+                height,
+                radius,
+            }
+            | Self::Column {
+                base,
+                height,
+                radius,
+            } => (
+                Vec3::new(-radius, *base, -radius),
+                Vec3::new(*radius, base + height, *radius),
+            ),
+            Self::Heightfield {
+                base,
+                size,
+                heights,
+            } => {
+                let top = heights.iter().flatten().fold(0.0f32, |top, h| top.max(*h));
+
+                (
+                    Vec3::new(-size.x / 2.0, *base, -size.y / 2.0),
+                    Vec3::new(size.x / 2.0, base + top, size.y / 2.0),
+                )
+            }
+            Self::Points(points) => return Ok(points.clone()),
+        };
+
+        let mut points = Vec::with_capacity(count);
+        let mut attempts = count.saturating_mul(Self::SAMPLE_ATTEMPTS);
+
+        while points.len() < count {
+            if attempts == 0 {
+                return Err(GenomeError::EmptyVolume { field: "envelope" });
+            }
+
+            attempts -= 1;
+
+            let point = Vec3::new(
+                rng.gen_range(min.x..=max.x),
+                rng.gen_range(min.y..=max.y),
+                rng.gen_range(min.z..=max.z),
+            );
+
+            if self.contains(point) {
+                points.push(point);
+            }
+        }
+
+        Ok(points)
+    }
+
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        match self {
+            Self::Ellipsoid { center, radii } => {
+                check_finite("envelope.center.x", center.x)?;
+                check_finite("envelope.center.y", center.y)?;
+                check_finite("envelope.center.z", center.z)?;
+                check_positive("envelope.radii.x", radii.x)?;
+                check_positive("envelope.radii.y", radii.y)?;
+                check_positive("envelope.radii.z", radii.z)?;
+            }
+            Self::Cone {
+                base,
+                height,
+                radius,
+            }
+            | Self::Column {
+                base,
+                height,
+                radius,
+            } => {
+                check_finite("envelope.base", *base)?;
+                check_positive("envelope.height", *height)?;
+                check_positive("envelope.radius", *radius)?;
+            }
+            Self::Heightfield {
+                base,
+                size,
+                heights,
+            } => {
+                check_finite("envelope.base", *base)?;
+                check_positive("envelope.size.x", size.x)?;
+                check_positive("envelope.size.y", size.y)?;
+                check_count("envelope.heights", heights.len(), 1)?;
+
+                let columns = heights[0].len();
+                check_count("envelope.heights", columns, 1)?;
+
+                for row in heights {
+                    if row.len() != columns {
+                        return Err(GenomeError::OutOfRange {
+                            field: "envelope.heights",
+                            value: row.len() as f64,
+                            min: columns as f64,
+                            max: columns as f64,
+                        });
+                    }
+
+                    for height in row {
+                        check_non_negative("envelope.heights", *height)?;
+                    }
+                }
+            }
+            Self::Points(points) => {
+                check_count("envelope.points", points.len(), 1)?;
+
+                for point in points {
+                    check_finite("envelope.points", point.x)?;
+                    check_finite("envelope.points", point.y)?;
+                    check_finite("envelope.points", point.z)?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Keeps a recursively split crown inside a shape, like a pruned hedge or topiary.
+///
+/// Branches are held to the shape once they've grown into it, so a bare trunk can
+/// reach a crown that starts above the ground. Genomes grown by [`Colonization`]
+/// fill the envelope of their attraction points instead and can't have one.
+///
+/// ```ron
+/// envelope: Some((
+///     shape: Column(base: 0.5, height: 8.0, radius: 0.8),
+///     response: BendBack(strength: 3.0),
+/// )),
+/// ```
+///
+/// [`Colonization`]: crate::colonization::Colonization
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Envelope {
+    pub shape: CrownEnvelope,
+    #[serde(default)]
+    pub response: EnvelopeResponse,
+}
+
+/// What happens to a branch about to grow out of its [`Envelope`].
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+pub enum EnvelopeResponse {
+    /// The branch is cut off where it would leave, and nothing splits off of it.
+    #[default]
+    Prune,
+    /// The branch turns back inward by `strength` per unit of length.
+    BendBack { strength: f32 },
+}
+
+impl Envelope {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        self.shape.validate()?;
+
+        if let EnvelopeResponse::BendBack { strength } = self.response {
+            check_positive("envelope.response.strength", strength)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Steers new branches away from space that already holds wood.
+///
+/// ```ron
+/// avoidance: Some(( cell: 0.5, strength: 2.0 )),
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Avoidance {
+    /// Size of the cells space is divided into.
+    pub cell: f32,
+    /// How strongly a branch turns away from occupied cells, per unit of length.
+    pub strength: f32,
+}
+
+impl Avoidance {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        check_positive("avoidance.cell", self.cell)?;
+        check_positive("avoidance.strength", self.strength)?;
+
+        Ok(())
+    }
+}
+
+/// A coarse grid of the space wood has grown into, each cell holding the branch
+/// that reached it first.
+pub struct OccupancyGrid {
+    cell: f32,
+    cells: HashMap<(i32, i32, i32), usize>,
+}
+
+impl OccupancyGrid {
+    pub fn new(cell: f32) -> Self {
+        Self {
+            cell,
+            cells: HashMap::default(),
+        }
+    }
+
+    fn key(&self, point: Vec3) -> (i32, i32, i32) {
+        let p = (point / self.cell).floor();
+
+        (p.x as i32, p.y as i32, p.z as i32)
+    }
+
+    /// Marks every cell the segments between `frames` pass through as holding `branch`.
+    pub fn insert(&mut self, branch: usize, frames: &[SegmentFrame]) {
+        for segment in frames.windows(2) {
+            let (a, b) = (segment[0].position, segment[1].position);
+            let steps = (a.distance(b) / self.cell * 2.0).ceil().max(1.0) as usize;
+
+            for step in 0..=steps {
+                let key = self.key(a.lerp(b, step as f32 / steps as f32));
+
+                self.cells.entry(key).or_insert(branch);
+            }
+        }
+    }
+
+    /// The direction away from the cells around `point` held by branches other
+    /// than those in `own`, zero if there are none.
+    ///
+    /// Cells around `start`, where the branch leaves its parent, are left out, as
+    /// its siblings and the branches its parent grows out of start there too.
+    pub fn repulsion(&self, point: Vec3, own: &[usize], start: Vec3) -> Vec3 {
+        let (x, y, z) = self.key(point);
+        let start = self.key(start);
+        let mut away = Vec3::ZERO;
+
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                for dz in -1..=1 {
+                    let key = (x + dx, y + dy, z + dz);
+
+                    if (key.0 - start.0).abs() <= 1
+                        && (key.1 - start.1).abs() <= 1
+                        && (key.2 - start.2).abs() <= 1
+                    {
+                        continue;
+                    }
+
+                    match self.cells.get(&key) {
+                        Some(branch) if !own.contains(branch) => {
+                            let center = (Vec3::new(key.0 as f32, key.1 as f32, key.2 as f32)
+                                + Vec3::splat(0.5))
+                                * self.cell;
+
+                            away += (point - center).normalize_or_zero();
+                        }
+                        _ => {}
+                    }
+                }
+            }
+        }
+
+        away.normalize_or_zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+
+    fn heightfield() -> CrownEnvelope {
+        CrownEnvelope::Heightfield {
+            base: 1.0,
+            size: Vec2::new(2.0, 2.0),
+            heights: vec![vec![0.0, 2.0], vec![0.0, 2.0]],
+        }
+    }
+
+    #[test]
+    fn envelopes_contain_the_points_inside_them() {
+        let ellipsoid = CrownEnvelope::Ellipsoid {
+            center: Vec3::Y * 3.0,
+            radii: Vec3::new(1.0, 2.0, 1.0),
+        };
+        let cone = CrownEnvelope::Cone {
+            base: 1.0,
+            height: 4.0,
+            radius: 2.0,
+        };
+        let column = CrownEnvelope::Column {
+            base: 1.0,
+            height: 4.0,
+            radius: 2.0,
+        };
+
+        assert!(ellipsoid.contains(Vec3::new(0.0, 4.9, 0.0)));
+        assert!(!ellipsoid.contains(Vec3::new(1.0, 4.0, 0.0)));
+        assert!(cone.contains(Vec3::new(1.9, 1.0, 0.0)));
+        assert!(cone.contains(Vec3::new(0.9, 3.0, 0.0)));
+        assert!(!cone.contains(Vec3::new(1.1, 3.0, 0.0)));
+        assert!(!cone.contains(Vec3::new(0.0, 0.9, 0.0)));
+        assert!(column.contains(Vec3::new(1.9, 4.9, 0.0)));
+        assert!(!column.contains(Vec3::new(0.0, 5.1, 0.0)));
+    }
+
+    #[test]
+    fn heightfields_blend_between_their_heights() {
+        let heightfield = heightfield();
+
+        // half way along x the top is half way between the rows' 0 and 2
+        assert!(heightfield.contains(Vec3::new(0.0, 1.9, 0.5)));
+        assert!(!heightfield.contains(Vec3::new(0.0, 2.1, 0.5)));
+        assert!(heightfield.contains(Vec3::new(1.0, 2.9, -1.0)));
+        assert!(!heightfield.contains(Vec3::new(1.1, 1.5, 0.0)));
+        assert!(!heightfield.contains(Vec3::new(0.0, 0.9, 0.0)));
+    }
+
+    #[test]
+    fn samples_lie_inside_the_envelope() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let cone = CrownEnvelope::Cone {
+            base: 1.0,
+            height: 4.0,
+            radius: 2.0,
+        };
+
+        for envelope in [cone, heightfield()] {
+            let points = envelope.sample(200, &mut rng).unwrap();
+
+            assert_eq!(points.len(), 200);
+            assert!(points.iter().all(|&point| envelope.contains(point)));
+        }
+
+        let points = vec![Vec3::X, Vec3::Y];
+
+        assert_eq!(
+            CrownEnvelope::Points(points.clone()).sample(10, &mut rng),
+            Ok(points)
+        );
+    }
+
+    #[test]
+    fn repulsion_points_away_from_other_branches() {
+        let frame = |y: f32| SegmentFrame {
+            position: Vec3::new(0.5, y, 0.5),
+            rotation: Quat::IDENTITY,
+            radius: 0.1,
+            sway: y,
+        };
+        let mut grid = OccupancyGrid::new(1.0);
+        grid.insert(0, &[frame(0.0), frame(6.0)]);
+
+        let point = Vec3::new(1.5, 4.5, 0.5);
+        let far = Vec3::new(10.0, 0.0, 0.0);
+        let away = grid.repulsion(point, &[1], far);
+
+        assert!((away - Vec3::X).length() < 0.0001);
+        assert_eq!(grid.repulsion(point, &[0, 1], far), Vec3::ZERO);
+        // cells next to where the branch starts are left out
+        assert_eq!(
+            grid.repulsion(point, &[1], Vec3::new(1.5, 4.5, 0.5)),
+            Vec3::ZERO
+        );
+    }
+}
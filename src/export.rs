@@ -0,0 +1,553 @@
+use crate::organ::OrganKind;
+use bevy::{
+    prelude::*,
+    render::mesh::{Indices, VertexAttributeValues},
+};
+use serde_json::json;
+use std::io::Write;
+use std::path::Path;
+
+#[derive(Debug)]
+pub enum ExportError {
+    MissingAttribute(&'static str),
+    MissingIndices,
+    UnsupportedFormat(String),
+    Io(std::io::Error),
+    Json(serde_json::Error),
+}
+
+impl std::fmt::Display for ExportError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::MissingAttribute(name) => write!(f, "mesh has no `{}` attribute", name),
+            Self::MissingIndices => write!(f, "mesh has no indices"),
+            Self::UnsupportedFormat(extension) => {
+                write!(
+                    f,
+                    "can't export to '{}', expected obj, gltf or glb",
+                    extension
+                )
+            }
+            Self::Io(e) => write!(f, "{}", e),
+            Self::Json(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for ExportError {}
+
+impl From<std::io::Error> for ExportError {
+    fn from(e: std::io::Error) -> Self {
+        Self::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for ExportError {
+    fn from(e: serde_json::Error) -> Self {
+        Self::Json(e)
+    }
+}
+
+/// The vertex streams of a plant mesh, in the layout the plant shaders expect.
+pub struct PlantMeshData {
+    pub positions: Vec<[f32; 3]>,
+    pub normals: Vec<[f32; 3]>,
+    pub uvs: Vec<[f32; 2]>,
+    pub colors: Vec<[f32; 4]>,
+    pub sway: Vec<f32>,
+    pub material: Vec<u32>,
+    pub indices: Vec<u32>,
+}
+
+macro_rules! attribute {
+    ($mesh:expr, $name:expr, $variant:ident) => {
+        match $mesh.attribute($name) {
+            Some(VertexAttributeValues::$variant(values)) => values.clone(),
+            _ => return Err(ExportError::MissingAttribute($name)),
+        }
+    };
+}
+
+impl PlantMeshData {
+    pub fn from_mesh(mesh: &Mesh) -> Result<Self, ExportError> {
+        let indices = match mesh.indices() {
+            Some(Indices::U32(indices)) => indices.clone(),
+            Some(Indices::U16(indices)) => indices.iter().map(|&i| i as u32).collect(),
+            None => return Err(ExportError::MissingIndices),
+        };
+
+        Ok(Self {
+            positions: attribute!(mesh, Mesh::ATTRIBUTE_POSITION, Float3),
+            normals: attribute!(mesh, Mesh::ATTRIBUTE_NORMAL, Float3),
+            uvs: attribute!(mesh, Mesh::ATTRIBUTE_UV_0, Float2),
+            colors: attribute!(mesh, "Vertex_Color", Float4),
+            sway: attribute!(mesh, "Plant_Sway", Float),
+            material: attribute!(mesh, "Plant_Material", Uint),
+            indices,
+        })
+    }
+
+    /// Triangle indices grouped by the material of their first vertex, in material order.
+    pub fn primitives(&self) -> Vec<(u32, Vec<u32>)> {
+        let mut primitives: Vec<(u32, Vec<u32>)> = Vec::new();
+
+        for triangle in self.indices.chunks_exact(3) {
+            let material = self.material[triangle[0] as usize];
+
+            match primitives.iter_mut().find(|(m, _)| *m == material) {
+                Some((_, indices)) => indices.extend_from_slice(triangle),
+                None => primitives.push((material, triangle.to_vec())),
+            }
+        }
+
+        primitives.sort_by_key(|(material, _)| *material);
+        primitives
+    }
+}
+
+pub fn material_name(material: u32) -> String {
+    match material {
+        0 => String::from("bark"),
+        1 => String::from("leaf"),
+        n => match OrganKind::from_material(n) {
+            Some(kind) => String::from(kind.name()),
+            None => format!("material_{}", n),
+        },
+    }
+}
+
+/// Writes `mesh` to `path`, picking the format from the file extension.
+pub fn export_mesh(mesh: &Mesh, path: &Path) -> Result<(), ExportError> {
+    let extension = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    match extension.as_str() {
+        "obj" => write_obj(mesh, path),
+        "gltf" => write_gltf(mesh, path),
+        "glb" => write_glb(mesh, path),
+        _ => Err(ExportError::UnsupportedFormat(extension)),
+    }
+}
+
+/// Writes a Wavefront `.obj` with vertex colors and a `.mtl` next to it.
+pub fn write_obj(mesh: &Mesh, path: &Path) -> Result<(), ExportError> {
+    let data = PlantMeshData::from_mesh(mesh)?;
+    let mtl_path = path.with_extension("mtl");
+    let primitives = data.primitives();
+
+    let mut obj = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    if let Some(name) = mtl_path.file_name() {
+        writeln!(obj, "mtllib {}", name.to_string_lossy())?;
+    }
+
+    for (p, c) in data.positions.iter().zip(&data.colors) {
+        writeln!(
+            obj,
+            "v {} {} {} {} {} {}",
+            p[0], p[1], p[2], c[0], c[1], c[2]
+        )?;
+    }
+
+    for uv in &data.uvs {
+        writeln!(obj, "vt {} {}", uv[0], 1.0 - uv[1])?;
+    }
+
+    for n in &data.normals {
+        writeln!(obj, "vn {} {} {}", n[0], n[1], n[2])?;
+    }
+
+    for (material, indices) in &primitives {
+        writeln!(obj, "usemtl {}", material_name(*material))?;
+
+        for triangle in indices.chunks_exact(3) {
+            let [a, b, c] = [triangle[0] + 1, triangle[1] + 1, triangle[2] + 1];
+
+            writeln!(obj, "f {0}/{0}/{0} {1}/{1}/{1} {2}/{2}/{2}", a, b, c)?;
+        }
+    }
+
+    obj.flush()?;
+
+    let mut mtl = std::io::BufWriter::new(std::fs::File::create(&mtl_path)?);
+
+    for (material, _) in &primitives {
+        writeln!(mtl, "newmtl {}", material_name(*material))?;
+        writeln!(mtl, "Kd 1.0 1.0 1.0")?;
+        writeln!(mtl, "Ks 0.0 0.0 0.0")?;
+        writeln!(mtl)?;
+    }
+
+    mtl.flush()?;
+
+    Ok(())
+}
+
+/// The images the plant shader samples, in the order of [`texture`].
+const TEXTURES: [&[u8]; 4] = [
+    include_bytes!("../assets/textures/bark.png"),
+    include_bytes!("../assets/textures/leaf_front.png"),
+    include_bytes!("../assets/textures/blossom.png"),
+    include_bytes!("../assets/textures/fruit.png"),
+];
+
+/// The image in [`TEXTURES`] the plant shader samples for `material`, and the alpha
+/// below which it cuts the card out, if it does.
+fn texture(material: u32) -> (usize, Option<f64>) {
+    match material {
+        0 => (0, None),
+        1 => (1, Some(0.9)),
+        2 => (2, Some(0.5)),
+        _ => (3, None),
+    }
+}
+
+const FLOAT: u32 = 5126;
+const UNSIGNED_INT: u32 = 5125;
+const ARRAY_BUFFER: u32 = 34962;
+const ELEMENT_ARRAY_BUFFER: u32 = 34963;
+
+struct GltfBuilder {
+    bin: Vec<u8>,
+    views: Vec<serde_json::Value>,
+    accessors: Vec<serde_json::Value>,
+}
+
+impl GltfBuilder {
+    /// Appends `bytes` as a new buffer view, `target` is `None` for images.
+    fn push(&mut self, bytes: &[u8], target: Option<u32>) -> usize {
+        // accessors must start at a multiple of their component size
+        while !self.bin.len().is_multiple_of(4) {
+            self.bin.push(0);
+        }
+
+        let offset = self.bin.len();
+
+        self.bin.extend_from_slice(bytes);
+
+        let mut view = json!({
+            "buffer": 0,
+            "byteOffset": offset,
+            "byteLength": bytes.len(),
+        });
+
+        if let Some(target) = target {
+            view["target"] = json!(target);
+        }
+
+        self.views.push(view);
+        self.views.len() - 1
+    }
+
+    fn accessor(&mut self, view: usize, component: u32, count: usize, ty: &str) -> usize {
+        self.accessors.push(json!({
+            "bufferView": view,
+            "componentType": component,
+            "count": count,
+            "type": ty,
+        }));
+
+        self.accessors.len() - 1
+    }
+
+    fn floats<const N: usize>(&mut self, values: &[[f32; N]], ty: &str) -> usize {
+        let bytes = values
+            .iter()
+            .flatten()
+            .flat_map(|v| v.to_le_bytes())
+            .collect::<Vec<u8>>();
+
+        let view = self.push(&bytes, Some(ARRAY_BUFFER));
+        self.accessor(view, FLOAT, values.len(), ty)
+    }
+}
+
+/// Builds the glTF document and its binary buffer, `uri` is where the buffer will live,
+/// or `None` when it is embedded in a `.glb`.
+fn build_gltf(data: &PlantMeshData, uri: Option<String>) -> (serde_json::Value, Vec<u8>) {
+    let mut builder = GltfBuilder {
+        bin: Vec::new(),
+        views: Vec::new(),
+        accessors: Vec::new(),
+    };
+
+    let position = builder.floats(&data.positions, "VEC3");
+    let (min, max) =
+        data.positions
+            .iter()
+            .fold(([f32::MAX; 3], [f32::MIN; 3]), |(mut min, mut max), p| {
+                for i in 0..3 {
+                    min[i] = min[i].min(p[i]);
+                    max[i] = max[i].max(p[i]);
+                }
+
+                (min, max)
+            });
+    builder.accessors[position]["min"] = json!(min);
+    builder.accessors[position]["max"] = json!(max);
+
+    let normal = builder.floats(&data.normals, "VEC3");
+    let uv = builder.floats(&data.uvs, "VEC2");
+    let color = builder.floats(&data.colors, "VEC4");
+    let sway = builder.floats(
+        &data.sway.iter().map(|&s| [s]).collect::<Vec<_>>(),
+        "SCALAR",
+    );
+    // custom attributes can't be integers in glTF
+    let material = builder.floats(
+        &data
+            .material
+            .iter()
+            .map(|&m| [m as f32])
+            .collect::<Vec<_>>(),
+        "SCALAR",
+    );
+
+    let mut materials = Vec::new();
+    let mut primitives = Vec::new();
+    let mut images = Vec::new();
+    // the glTF image of each of `TEXTURES`, added when a material first uses it
+    let mut textures = [None; TEXTURES.len()];
+
+    for (id, indices) in data.primitives() {
+        let bytes = indices
+            .iter()
+            .flat_map(|i| i.to_le_bytes())
+            .collect::<Vec<u8>>();
+        let view = builder.push(&bytes, Some(ELEMENT_ARRAY_BUFFER));
+        let accessor = builder.accessor(view, UNSIGNED_INT, indices.len(), "SCALAR");
+
+        let (image, cutoff) = texture(id);
+        let texture = *textures[image].get_or_insert_with(|| {
+            let view = builder.push(TEXTURES[image], None);
+
+            images.push(json!({ "bufferView": view, "mimeType": "image/png" }));
+            images.len() - 1
+        });
+
+        let mut material_json = json!({
+            "name": material_name(id),
+            "doubleSided": true,
+            "pbrMetallicRoughness": {
+                "baseColorFactor": [1.0, 1.0, 1.0, 1.0],
+                "baseColorTexture": { "index": texture },
+                "metallicFactor": 0.0,
+                "roughnessFactor": 1.0,
+            },
+        });
+
+        // leaves and blossoms are cut out of their cards
+        if let Some(cutoff) = cutoff {
+            material_json["alphaMode"] = json!("MASK");
+            material_json["alphaCutoff"] = json!(cutoff);
+        }
+
+        materials.push(material_json);
+        primitives.push(json!({
+            "attributes": {
+                "POSITION": position,
+                "NORMAL": normal,
+                "TEXCOORD_0": uv,
+                "COLOR_0": color,
+                "_PLANT_SWAY": sway,
+                "_PLANT_MATERIAL": material,
+            },
+            "indices": accessor,
+            "material": materials.len() - 1,
+        }));
+    }
+
+    let mut buffer = json!({ "byteLength": builder.bin.len() });
+
+    if let Some(uri) = uri {
+        buffer["uri"] = json!(uri);
+    }
+
+    let document = json!({
+        "asset": { "version": "2.0", "generator": "tree" },
+        "scene": 0,
+        "scenes": [{ "nodes": [0] }],
+        "nodes": [{ "name": "plant", "mesh": 0 }],
+        "meshes": [{ "name": "plant", "primitives": primitives }],
+        "materials": materials,
+        // one texture per image, at the same index
+        "textures": (0..images.len())
+            .map(|image| json!({ "source": image, "sampler": 0 }))
+            .collect::<Vec<_>>(),
+        "images": images,
+        // repeating, like the plant shader's samplers
+        "samplers": [{}],
+        "buffers": [buffer],
+        "bufferViews": builder.views,
+        "accessors": builder.accessors,
+    });
+
+    (document, builder.bin)
+}
+
+/// Writes a `.gltf` document with its buffer and textures in a `.bin` file next to it.
+pub fn write_gltf(mesh: &Mesh, path: &Path) -> Result<(), ExportError> {
+    let data = PlantMeshData::from_mesh(mesh)?;
+    let bin_path = path.with_extension("bin");
+    let uri = bin_path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned());
+
+    let (document, bin) = build_gltf(&data, uri);
+
+    std::fs::write(path, serde_json::to_vec_pretty(&document)?)?;
+    std::fs::write(bin_path, bin)?;
+
+    Ok(())
+}
+
+/// Writes a self contained binary `.glb`, textures included.
+pub fn write_glb(mesh: &Mesh, path: &Path) -> Result<(), ExportError> {
+    let data = PlantMeshData::from_mesh(mesh)?;
+    let (document, mut bin) = build_gltf(&data, None);
+
+    let mut json = serde_json::to_vec(&document)?;
+
+    while json.len() % 4 != 0 {
+        json.push(b' ');
+    }
+
+    while bin.len() % 4 != 0 {
+        bin.push(0);
+    }
+
+    let length = 12 + 8 + json.len() + 8 + bin.len();
+
+    let mut glb = std::io::BufWriter::new(std::fs::File::create(path)?);
+
+    glb.write_all(b"glTF")?;
+    glb.write_all(&2u32.to_le_bytes())?;
+    glb.write_all(&(length as u32).to_le_bytes())?;
+
+    glb.write_all(&(json.len() as u32).to_le_bytes())?;
+    glb.write_all(b"JSON")?;
+    glb.write_all(&json)?;
+
+    glb.write_all(&(bin.len() as u32).to_le_bytes())?;
+    glb.write_all(b"BIN\0")?;
+    glb.write_all(&bin)?;
+
+    glb.flush()?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::pipeline::PrimitiveTopology;
+
+    /// A bark triangle and a leaf triangle sharing an edge, the leaf first.
+    fn mesh() -> Mesh {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [0.0, 2.0, 0.0],
+                [1.0, 2.0, -1.0],
+            ],
+        );
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, vec![[0.0, 0.0, 1.0]; 4]);
+        mesh.set_attribute(Mesh::ATTRIBUTE_UV_0, vec![[0.0, 0.0]; 4]);
+        mesh.set_attribute("Vertex_Color", vec![[1.0, 1.0, 1.0, 1.0]; 4]);
+        mesh.set_attribute("Plant_Sway", vec![0.0; 4]);
+        mesh.set_attribute("Plant_Material", vec![0u32, 0, 0, 1]);
+        mesh.set_indices(Some(Indices::U32(vec![3, 2, 1, 0, 1, 2])));
+
+        mesh
+    }
+
+    fn temp_path(file: &str) -> std::path::PathBuf {
+        std::env::temp_dir().join(format!("tree-export-{}-{}", std::process::id(), file))
+    }
+
+    #[test]
+    fn primitives_are_grouped_by_material() {
+        let data = PlantMeshData::from_mesh(&mesh()).unwrap();
+
+        assert_eq!(data.primitives(), [(0, vec![0, 1, 2]), (1, vec![3, 2, 1])]);
+    }
+
+    #[test]
+    fn meshes_without_plant_attributes_are_refused() {
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+
+        assert!(matches!(
+            PlantMeshData::from_mesh(&mesh),
+            Err(ExportError::MissingIndices)
+        ));
+
+        mesh.set_indices(Some(Indices::U16(vec![0, 1, 2])));
+
+        assert!(matches!(
+            PlantMeshData::from_mesh(&mesh),
+            Err(ExportError::MissingAttribute(Mesh::ATTRIBUTE_POSITION))
+        ));
+        assert!(matches!(
+            export_mesh(&self::mesh(), "plant.fbx".as_ref()),
+            Err(ExportError::UnsupportedFormat(e)) if e == "fbx"
+        ));
+    }
+
+    #[test]
+    fn obj_faces_use_their_materials() {
+        let path = temp_path("plant.obj");
+
+        export_mesh(&mesh(), &path).unwrap();
+
+        let obj = std::fs::read_to_string(&path).unwrap();
+        let mtl = std::fs::read_to_string(path.with_extension("mtl")).unwrap();
+        let lines = |prefix: &str| obj.lines().filter(|l| l.starts_with(prefix)).count();
+
+        assert_eq!(lines("v "), 4);
+        assert_eq!(lines("vt "), 4);
+        assert_eq!(lines("vn "), 4);
+        assert!(obj.contains("usemtl bark\nf 1/1/1 2/2/2 3/3/3\n"));
+        assert!(obj.contains("usemtl leaf\nf 4/4/4 3/3/3 2/2/2\n"));
+        assert_eq!(mtl.matches("newmtl").count(), 2);
+
+        std::fs::remove_file(path.with_extension("mtl")).unwrap();
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn glb_chunks_hold_the_document_and_its_buffer() {
+        let path = temp_path("plant.glb");
+
+        export_mesh(&mesh(), &path).unwrap();
+
+        let glb = std::fs::read(&path).unwrap();
+        std::fs::remove_file(path).unwrap();
+
+        let u32_at = |i: usize| u32::from_le_bytes([glb[i], glb[i + 1], glb[i + 2], glb[i + 3]]);
+        let json_length = u32_at(12) as usize;
+        let bin = 20 + json_length;
+
+        assert_eq!(&glb[..4], b"glTF");
+        assert_eq!(u32_at(8) as usize, glb.len());
+        assert_eq!(&glb[16..20], b"JSON");
+        assert_eq!(&glb[bin + 4..bin + 8], b"BIN\0");
+        assert_eq!(bin + 8 + u32_at(bin) as usize, glb.len());
+
+        let document: serde_json::Value = serde_json::from_slice(&glb[20..bin]).unwrap();
+        let buffer = &document["buffers"][0];
+        let primitives = document["meshes"][0]["primitives"].as_array().unwrap();
+
+        assert!(buffer.get("uri").is_none());
+        assert!(buffer["byteLength"].as_u64().unwrap() <= u32_at(bin) as u64);
+        assert_eq!(primitives.len(), 2);
+        assert_eq!(document["accessors"][0]["max"], json!([1.0, 2.0, 0.0]));
+        assert_eq!(document["materials"][0]["name"], "bark");
+        assert_eq!(document["materials"][1]["alphaMode"], "MASK");
+    }
+}
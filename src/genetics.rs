@@ -0,0 +1,948 @@
+use crate::colonization::*;
+use crate::curve::*;
+use crate::envelope::*;
+use crate::level::*;
+use crate::organ::*;
+use crate::phyllotaxis::*;
+use crate::plant::{Genome, GenomeError};
+use crate::roots::*;
+use crate::skeleton::Growth;
+use crate::tropism::*;
+use bevy::prelude::*;
+use rand::prelude::*;
+
+/// A gene that blends smoothly between values, mutations nudge it by a fraction of its range.
+#[derive(Clone, Copy, Debug)]
+pub struct Continuous {
+    pub min: f32,
+    pub max: f32,
+}
+
+impl Continuous {
+    pub const fn new(min: f32, max: f32) -> Self {
+        Self { min, max }
+    }
+
+    pub fn crossover(self, a: f32, b: f32, rng: &mut impl Rng) -> f32 {
+        if rng.gen() {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// A value authored outside of the range of the gene can mutate, but not any
+    /// further out.
+    pub fn mutate(self, value: f32, rate: f32, rng: &mut impl Rng) -> f32 {
+        if rng.gen_range(0.0..1.0) >= rate {
+            return value;
+        }
+
+        let step = (self.max - self.min) * 0.1;
+
+        (value + rng.gen_range(-step..=step)).clamp(self.min.min(value), self.max.max(value))
+    }
+
+    /// Blends from `a` at 0 to `b` at 1, `t` outside of that is clamped. The result
+    /// stays in the range of the gene, widened to take in both values.
+    pub fn lerp(self, a: f32, b: f32, t: f32) -> f32 {
+        let t = t.clamp(0.0, 1.0);
+
+        (a + (b - a) * t).clamp(self.min.min(a).min(b), self.max.max(a).max(b))
+    }
+
+    /// Mutates a constant or every key value of a curve.
+    pub fn mutate_param(self, param: &Param, rate: f32, rng: &mut impl Rng) -> Param {
+        match param {
+            Param::Constant(value) => Param::Constant(self.mutate(*value, rate, rng)),
+            Param::Curve(curve) => Param::Curve(Curve {
+                keys: curve
+                    .keys
+                    .iter()
+                    .map(|&(position, value)| (position, self.mutate(value, rate, rng)))
+                    .collect(),
+                ..curve.clone()
+            }),
+        }
+    }
+
+    /// Blends key by key, a constant blends into a curve as if it were flat. Curves
+    /// that don't line up aren't blended.
+    pub fn lerp_param(self, a: &Param, b: &Param, t: f32) -> Param {
+        let blend = |curve: &Curve, other: &dyn Fn(usize) -> f32, t: f32| {
+            Param::Curve(Curve {
+                keys: curve
+                    .keys
+                    .iter()
+                    .enumerate()
+                    .map(|(i, &(position, value))| (position, self.lerp(value, other(i), t)))
+                    .collect(),
+                ..curve.clone()
+            })
+        };
+
+        match (a, b) {
+            (Param::Constant(a), Param::Constant(b)) => Param::Constant(self.lerp(*a, *b, t)),
+            (Param::Curve(a), Param::Constant(b)) => blend(a, &|_| *b, t),
+            (Param::Constant(a), Param::Curve(b)) => blend(b, &|_| *a, 1.0 - t),
+            (Param::Curve(curve), Param::Curve(other))
+                if curve.along == other.along
+                    && curve
+                        .keys
+                        .iter()
+                        .map(|key| key.0)
+                        .eq(other.keys.iter().map(|key| key.0)) =>
+            {
+                blend(curve, &|i| other.keys[i].1, t)
+            }
+            _ if t < 0.5 => a.clone(),
+            _ => b.clone(),
+        }
+    }
+}
+
+/// A gene that only takes whole values, mutations step it up or down by one.
+#[derive(Clone, Copy, Debug)]
+pub struct Discrete {
+    pub min: usize,
+    pub max: usize,
+}
+
+impl Discrete {
+    pub const fn new(min: usize, max: usize) -> Self {
+        Self { min, max }
+    }
+
+    pub fn crossover(self, a: usize, b: usize, rng: &mut impl Rng) -> usize {
+        if rng.gen() {
+            a
+        } else {
+            b
+        }
+    }
+
+    /// A value authored outside of the range of the gene can mutate, but not any
+    /// further out.
+    pub fn mutate(self, value: usize, rate: f32, rng: &mut impl Rng) -> usize {
+        if rng.gen_range(0.0..1.0) >= rate {
+            return value;
+        }
+
+        let mutated = if rng.gen() {
+            value.saturating_add(1)
+        } else {
+            value.saturating_sub(1)
+        };
+
+        mutated.clamp(self.min.min(value), self.max.max(value))
+    }
+
+    /// Blends from `a` at 0 to `b` at 1 and rounds, `t` outside of that is clamped.
+    /// The result stays in the range of the gene, widened to take in both values.
+    pub fn lerp(self, a: usize, b: usize, t: f32) -> usize {
+        let t = t.clamp(0.0, 1.0);
+
+        ((a as f32 + (b as f32 - a as f32) * t).round() as usize)
+            .clamp(self.min.min(a).min(b), self.max.max(a).max(b))
+    }
+}
+
+/// Values that can be bred, mutated and blended.
+///
+/// The default methods treat the value as a single discrete gene, so it is passed on whole.
+pub trait Genetic: Clone {
+    fn crossover(a: &Self, b: &Self, rng: &mut impl Rng) -> Self {
+        if rng.gen() {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    }
+
+    fn mutate(&self, _rate: f32, _rng: &mut impl Rng) -> Self {
+        self.clone()
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        if t < 0.5 {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    }
+}
+
+impl<T: Genetic> Genetic for Option<T> {
+    fn crossover(a: &Self, b: &Self, rng: &mut impl Rng) -> Self {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(T::crossover(a, b, rng)),
+            _ if rng.gen() => a.clone(),
+            _ => b.clone(),
+        }
+    }
+
+    fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Self {
+        self.as_ref().map(|value| value.mutate(rate, rng))
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(T::lerp(a, b, t)),
+            _ if t < 0.5 => a.clone(),
+            _ => b.clone(),
+        }
+    }
+}
+
+/// Elements are bred pairwise, the longer parent passes on the rest.
+impl<T: Genetic> Genetic for Vec<T> {
+    fn crossover(a: &Self, b: &Self, rng: &mut impl Rng) -> Self {
+        let longer = if a.len() > b.len() { a } else { b };
+        let mut child = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| T::crossover(a, b, rng))
+            .collect::<Vec<_>>();
+
+        if rng.gen() {
+            child.extend(longer[child.len()..].iter().cloned());
+        }
+
+        child
+    }
+
+    fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Self {
+        self.iter().map(|value| value.mutate(rate, rng)).collect()
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        let longer = if a.len() > b.len() { a } else { b };
+        let mut blend = a
+            .iter()
+            .zip(b)
+            .map(|(a, b)| T::lerp(a, b, t))
+            .collect::<Vec<_>>();
+
+        if (longer.len() == a.len()) == (t < 0.5) {
+            blend.extend(longer[blend.len()..].iter().cloned());
+        }
+
+        blend
+    }
+}
+
+impl Genetic for Param {}
+
+impl Genetic for Phyllotaxis {}
+
+pub const MAX_SPLITS: Discrete = Discrete::new(2, 8);
+pub const BRANCHES_PER_SPLIT: Discrete = Discrete::new(1, 8);
+pub const STARTING_RADIUS: Continuous = Continuous::new(0.01, 2.0);
+pub const RADIAL_SEGMENTS: Discrete = Discrete::new(3, 32);
+pub const BRANCH_LENGTH: Continuous = Continuous::new(0.1, 10.0);
+pub const SEGMENTS_PER_BRANCH: Discrete = Discrete::new(1, 16);
+pub const RADIUS_SUSTAIN: Continuous = Continuous::new(0.0, 1.0);
+pub const LEAF_START: Discrete = Discrete::new(0, 8);
+pub const LEAF_DENSITY: Continuous = Continuous::new(0.0, 100.0);
+pub const LEAF_SIZE: Continuous = Continuous::new(0.0, 4.0);
+pub const LEAF_LENGTH: Continuous = Continuous::new(0.0, 8.0);
+pub const LEAF_OFFSET: Continuous = Continuous::new(0.01, 4.0);
+pub const BRANCH_DECAY: Discrete = Discrete::new(0, 4);
+pub const BRANCH_BEND: Continuous = Continuous::new(0.01, std::f32::consts::PI);
+pub const BRANCH_SWAY: Continuous = Continuous::new(0.01, std::f32::consts::TAU);
+pub const BRANCH_TWIST: Continuous = Continuous::new(0.0, std::f32::consts::PI);
+
+/// How a single field of a [`Genome`] is bred, so its fields can be listed once along
+/// with their genes.
+trait Gene<T> {
+    fn crossover(&self, a: &T, b: &T, rng: &mut impl Rng) -> T;
+    fn mutate(&self, value: &T, rate: f32, rng: &mut impl Rng) -> T;
+    fn lerp(&self, a: &T, b: &T, t: f32) -> T;
+}
+
+impl Gene<f32> for Continuous {
+    fn crossover(&self, a: &f32, b: &f32, rng: &mut impl Rng) -> f32 {
+        Continuous::crossover(*self, *a, *b, rng)
+    }
+
+    fn mutate(&self, value: &f32, rate: f32, rng: &mut impl Rng) -> f32 {
+        Continuous::mutate(*self, *value, rate, rng)
+    }
+
+    fn lerp(&self, a: &f32, b: &f32, t: f32) -> f32 {
+        Continuous::lerp(*self, *a, *b, t)
+    }
+}
+
+impl Gene<Param> for Continuous {
+    fn crossover(&self, a: &Param, b: &Param, rng: &mut impl Rng) -> Param {
+        Genetic::crossover(a, b, rng)
+    }
+
+    fn mutate(&self, value: &Param, rate: f32, rng: &mut impl Rng) -> Param {
+        self.mutate_param(value, rate, rng)
+    }
+
+    fn lerp(&self, a: &Param, b: &Param, t: f32) -> Param {
+        self.lerp_param(a, b, t)
+    }
+}
+
+impl Gene<usize> for Discrete {
+    fn crossover(&self, a: &usize, b: &usize, rng: &mut impl Rng) -> usize {
+        Discrete::crossover(*self, *a, *b, rng)
+    }
+
+    fn mutate(&self, value: &usize, rate: f32, rng: &mut impl Rng) -> usize {
+        Discrete::mutate(*self, *value, rate, rng)
+    }
+
+    fn lerp(&self, a: &usize, b: &usize, t: f32) -> usize {
+        Discrete::lerp(*self, *a, *b, t)
+    }
+}
+
+/// Both ends of the range are genes of their own.
+impl Gene<std::ops::Range<usize>> for Discrete {
+    fn crossover(
+        &self,
+        a: &std::ops::Range<usize>,
+        b: &std::ops::Range<usize>,
+        rng: &mut impl Rng,
+    ) -> std::ops::Range<usize> {
+        if rng.gen() {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    }
+
+    fn mutate(
+        &self,
+        range: &std::ops::Range<usize>,
+        rate: f32,
+        rng: &mut impl Rng,
+    ) -> std::ops::Range<usize> {
+        let start = Discrete::mutate(*self, range.start, rate, rng);
+        let end = Discrete::mutate(*self, range.end, rate, rng);
+
+        start.min(end)..start.max(end)
+    }
+
+    fn lerp(
+        &self,
+        a: &std::ops::Range<usize>,
+        b: &std::ops::Range<usize>,
+        t: f32,
+    ) -> std::ops::Range<usize> {
+        Discrete::lerp(*self, a.start, b.start, t)..Discrete::lerp(*self, a.end, b.end, t)
+    }
+}
+
+/// The gene of a value made up of genes of its own, bred through its [`Genetic`] impl.
+struct Nested;
+
+impl<T: Genetic> Gene<T> for Nested {
+    fn crossover(&self, a: &T, b: &T, rng: &mut impl Rng) -> T {
+        T::crossover(a, b, rng)
+    }
+
+    fn mutate(&self, value: &T, rate: f32, rng: &mut impl Rng) -> T {
+        value.mutate(rate, rng)
+    }
+
+    fn lerp(&self, a: &T, b: &T, t: f32) -> T {
+        T::lerp(a, b, t)
+    }
+}
+
+/// A pinned seed mutates into a new random one, an unpinned one stays unpinned.
+struct SeedGene;
+
+impl Gene<Option<u64>> for SeedGene {
+    fn crossover(&self, a: &Option<u64>, b: &Option<u64>, rng: &mut impl Rng) -> Option<u64> {
+        if rng.gen() {
+            *a
+        } else {
+            *b
+        }
+    }
+
+    fn mutate(&self, seed: &Option<u64>, rate: f32, rng: &mut impl Rng) -> Option<u64> {
+        match seed {
+            Some(_) if rng.gen_range(0.0..1.0) < rate => Some(rng.gen()),
+            seed => *seed,
+        }
+    }
+
+    fn lerp(&self, a: &Option<u64>, b: &Option<u64>, t: f32) -> Option<u64> {
+        if t < 0.5 {
+            *a
+        } else {
+            *b
+        }
+    }
+}
+
+/// The gene of a value that may be left out. Left out on either side the value is
+/// passed on whole, otherwise it's bred with the gene `G`.
+struct Optional<G>(G);
+
+impl<T: Clone, G: Gene<T>> Gene<Option<T>> for Optional<G> {
+    fn crossover(&self, a: &Option<T>, b: &Option<T>, rng: &mut impl Rng) -> Option<T> {
+        if rng.gen() {
+            a.clone()
+        } else {
+            b.clone()
+        }
+    }
+
+    fn mutate(&self, value: &Option<T>, rate: f32, rng: &mut impl Rng) -> Option<T> {
+        value.as_ref().map(|value| self.0.mutate(value, rate, rng))
+    }
+
+    fn lerp(&self, a: &Option<T>, b: &Option<T>, t: f32) -> Option<T> {
+        match (a, b) {
+            (Some(a), Some(b)) => Some(self.0.lerp(a, b, t)),
+            _ if t < 0.5 => a.clone(),
+            _ => b.clone(),
+        }
+    }
+}
+
+/// Implements [`Genetic`] for a struct by breeding every field with its gene, the
+/// same way [`Genome`] is bred.
+macro_rules! genetic_fields {
+    ($ty:ty { $($field:ident: $gene:expr,)* }) => {
+        impl Genetic for $ty {
+            fn crossover(a: &Self, b: &Self, rng: &mut impl Rng) -> Self {
+                Self {
+                    $($field: Gene::crossover(&$gene, &a.$field, &b.$field, rng),)*
+                }
+            }
+
+            fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Self {
+                Self {
+                    $($field: Gene::mutate(&$gene, &self.$field, rate, rng),)*
+                }
+            }
+
+            fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+                Self {
+                    $($field: Gene::lerp(&$gene, &a.$field, &b.$field, t),)*
+                }
+            }
+        }
+    };
+}
+
+macro_rules! genome_genetics {
+    ($($field:ident: $ty:ty, $kind:ident, $gene:expr;)*) => {
+        /// Genomes are bred like [`Genetic`] values, but validated afterwards, as fields
+        /// that are fine on their own can still make a genome that doesn't grow.
+        impl Genome {
+            pub fn crossover(a: &Self, b: &Self, rng: &mut impl Rng) -> Result<Self, GenomeError> {
+                let genome = Self {
+                    $($field: Gene::crossover(&$gene, &a.$field, &b.$field, rng),)*
+                    patch: None,
+                };
+
+                genome.validate()?;
+
+                Ok(genome)
+            }
+
+            pub fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Result<Self, GenomeError> {
+                let genome = Self {
+                    $($field: Gene::mutate(&$gene, &self.$field, rate, rng),)*
+                    patch: None,
+                };
+
+                genome.validate()?;
+
+                Ok(genome)
+            }
+
+            pub fn lerp(a: &Self, b: &Self, t: f32) -> Result<Self, GenomeError> {
+                let genome = Self {
+                    $($field: Gene::lerp(&$gene, &a.$field, &b.$field, t),)*
+                    patch: None,
+                };
+
+                genome.validate()?;
+
+                Ok(genome)
+            }
+        }
+    };
+}
+
+crate::plant::genome_fields!(genome_genetics);
+
+pub const ATTRACTION_POINTS: Discrete = Discrete::new(1, 10_000);
+pub const TRUNK_HEIGHT: Continuous = Continuous::new(0.0, 20.0);
+pub const STEP: Continuous = Continuous::new(0.01, 2.0);
+pub const INFLUENCE_RADIUS: Continuous = Continuous::new(0.01, 10.0);
+pub const KILL_RADIUS: Continuous = Continuous::new(0.01, 10.0);
+pub const MAX_ITERATIONS: Discrete = Discrete::new(1, 1000);
+pub const ENVELOPE_SIZE: Continuous = Continuous::new(0.01, 50.0);
+
+fn vec3_mutate(value: Vec3, gene: Continuous, rate: f32, rng: &mut impl Rng) -> Vec3 {
+    Vec3::new(
+        gene.mutate(value.x, rate, rng),
+        gene.mutate(value.y, rate, rng),
+        gene.mutate(value.z, rate, rng),
+    )
+}
+
+impl Genetic for CrownEnvelope {
+    fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Self {
+        match self {
+            Self::Ellipsoid { center, radii } => Self::Ellipsoid {
+                center: *center,
+                radii: vec3_mutate(*radii, ENVELOPE_SIZE, rate, rng),
+            },
+            Self::Cone {
+                base,
+                height,
+                radius,
+            } => Self::Cone {
+                base: *base,
+                height: ENVELOPE_SIZE.mutate(*height, rate, rng),
+                radius: ENVELOPE_SIZE.mutate(*radius, rate, rng),
+            },
+            Self::Column {
+                base,
+                height,
+                radius,
+            } => Self::Column {
+                base: *base,
+                height: ENVELOPE_SIZE.mutate(*height, rate, rng),
+                radius: ENVELOPE_SIZE.mutate(*radius, rate, rng),
+            },
+            Self::Heightfield {
+                base,
+                size,
+                heights,
+            } => Self::Heightfield {
+                base: *base,
+                size: Vec2::new(
+                    ENVELOPE_SIZE.mutate(size.x, rate, rng),
+                    ENVELOPE_SIZE.mutate(size.y, rate, rng),
+                ),
+                heights: heights.clone(),
+            },
+            Self::Points(points) => Self::Points(points.clone()),
+        }
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        let t = t.clamp(0.0, 1.0);
+
+        match (a, b) {
+            (
+                Self::Ellipsoid { center, radii },
+                Self::Ellipsoid {
+                    center: other_center,
+                    radii: other_radii,
+                },
+            ) => Self::Ellipsoid {
+                center: center.lerp(*other_center, t),
+                radii: radii.lerp(*other_radii, t),
+            },
+            (
+                Self::Cone {
+                    base,
+                    height,
+                    radius,
+                },
+                Self::Cone {
+                    base: other_base,
+                    height: other_height,
+                    radius: other_radius,
+                },
+            ) => Self::Cone {
+                base: base + (other_base - base) * t,
+                height: ENVELOPE_SIZE.lerp(*height, *other_height, t),
+                radius: ENVELOPE_SIZE.lerp(*radius, *other_radius, t),
+            },
+            (
+                Self::Column {
+                    base,
+                    height,
+                    radius,
+                },
+                Self::Column {
+                    base: other_base,
+                    height: other_height,
+                    radius: other_radius,
+                },
+            ) => Self::Column {
+                base: base + (other_base - base) * t,
+                height: ENVELOPE_SIZE.lerp(*height, *other_height, t),
+                radius: ENVELOPE_SIZE.lerp(*radius, *other_radius, t),
+            },
+            // heights are blended one by one, so the grids have to match
+            (
+                Self::Heightfield {
+                    base,
+                    size,
+                    heights,
+                },
+                Self::Heightfield {
+                    base: other_base,
+                    size: other_size,
+                    heights: other_heights,
+                },
+            ) if heights.len() == other_heights.len()
+                && heights
+                    .iter()
+                    .zip(other_heights)
+                    .all(|(row, other)| row.len() == other.len()) =>
+            {
+                Self::Heightfield {
+                    base: base + (other_base - base) * t,
+                    size: Vec2::new(
+                        ENVELOPE_SIZE.lerp(size.x, other_size.x, t),
+                        ENVELOPE_SIZE.lerp(size.y, other_size.y, t),
+                    ),
+                    heights: heights
+                        .iter()
+                        .zip(other_heights)
+                        .map(|(row, other)| {
+                            row.iter()
+                                .zip(other)
+                                .map(|(height, other)| height + (other - height) * t)
+                                .collect()
+                        })
+                        .collect(),
+                }
+            }
+            _ if t < 0.5 => a.clone(),
+            _ => b.clone(),
+        }
+    }
+}
+
+genetic_fields!(Envelope {
+    shape: Nested,
+    response: Nested,
+});
+
+impl Genetic for EnvelopeResponse {}
+
+impl Genetic for Avoidance {}
+
+genetic_fields!(Colonization {
+    envelope: Nested,
+    attraction_points: ATTRACTION_POINTS,
+    trunk_height: TRUNK_HEIGHT,
+    step: STEP,
+    influence_radius: INFLUENCE_RADIUS,
+    kill_radius: KILL_RADIUS,
+    max_iterations: MAX_ITERATIONS,
+});
+
+pub const GROWTH_RATE: Continuous = Continuous::new(0.01, 5.0);
+pub const MAX_AGE: Continuous = Continuous::new(1.0, 600.0);
+
+genetic_fields!(Growth {
+    rate: GROWTH_RATE,
+    max_age: MAX_AGE,
+});
+
+pub const PHOTOTROPISM: Continuous = Continuous::new(-2.0, 2.0);
+pub const GRAVITROPISM: Continuous = Continuous::new(-2.0, 2.0);
+
+/// A light direction is passed on as is and blended as a direction, opposite ones
+/// have no direction halfway so the nearer one is kept.
+struct LightGene;
+
+impl Gene<Option<Vec3>> for LightGene {
+    fn crossover(&self, a: &Option<Vec3>, b: &Option<Vec3>, rng: &mut impl Rng) -> Option<Vec3> {
+        if rng.gen() {
+            *a
+        } else {
+            *b
+        }
+    }
+
+    fn mutate(&self, light: &Option<Vec3>, _rate: f32, _rng: &mut impl Rng) -> Option<Vec3> {
+        *light
+    }
+
+    fn lerp(&self, a: &Option<Vec3>, b: &Option<Vec3>, t: f32) -> Option<Vec3> {
+        match (*a, *b) {
+            (Some(a), Some(b)) => Some(
+                a.normalize_or_zero()
+                    .lerp(b.normalize_or_zero(), t.clamp(0.0, 1.0))
+                    .try_normalize()
+                    .unwrap_or(if t < 0.5 { a } else { b })
+                    .normalize_or_zero(),
+            ),
+            _ if t < 0.5 => *a,
+            _ => *b,
+        }
+    }
+}
+
+genetic_fields!(Tropism {
+    phototropism: PHOTOTROPISM,
+    gravitropism: GRAVITROPISM,
+    light: LightGene,
+});
+
+pub const ROOT_COUNT: Discrete = Discrete::new(1, 16);
+pub const ROOT_LENGTH: Continuous = Continuous::new(0.1, 10.0);
+pub const ROOT_RADIUS: Continuous = Continuous::new(0.05, 1.5);
+pub const BUTTRESS: Continuous = Continuous::new(0.0, 4.0);
+pub const FLARE: Continuous = Continuous::new(0.0, 2.0);
+/// Short of a right angle, where roots would dive straight down.
+pub const ROOT_DIP: Continuous = Continuous::new(0.0, 1.5);
+pub const ROOT_SPLITS: Discrete = Discrete::new(0, 6);
+pub const ROOT_SUSTAIN: Continuous = Continuous::new(0.1, 1.0);
+pub const ROOT_SPREAD: Continuous = Continuous::new(0.0, std::f32::consts::PI);
+
+genetic_fields!(Roots {
+    count: ROOT_COUNT,
+    length: ROOT_LENGTH,
+    radius: ROOT_RADIUS,
+    buttress: BUTTRESS,
+    flare: FLARE,
+    dip: ROOT_DIP,
+    splits: ROOT_SPLITS,
+    branches_per_split: BRANCHES_PER_SPLIT,
+    sustain: ROOT_SUSTAIN,
+    spread: ROOT_SPREAD,
+    gravitropism: GRAVITROPISM,
+    segments: SEGMENTS_PER_BRANCH,
+    radial_segments: RADIAL_SEGMENTS,
+});
+
+genetic_fields!(LevelParams {
+    branches: Optional(BRANCHES_PER_SPLIT),
+    length: Optional(BRANCH_LENGTH),
+    bend: Optional(BRANCH_BEND),
+    twist: Optional(BRANCH_TWIST),
+    radial_segments: Optional(RADIAL_SEGMENTS),
+    leaf_density: Optional(LEAF_DENSITY),
+    leaf_size: Optional(LEAF_SIZE),
+    leaf_length: Optional(LEAF_LENGTH),
+    leaf_offset: Optional(LEAF_OFFSET),
+});
+
+/// The most a mutation scales the size of an organ by. Organs are authored at any
+/// scale, so their size is scaled instead of held to a range.
+pub const ORGAN_SIZE_STEP: f32 = 0.1;
+
+/// Organs are passed on whole, only their size mutates and blends.
+impl Genetic for OrganParams {
+    fn mutate(&self, rate: f32, rng: &mut impl Rng) -> Self {
+        let size = if rng.gen_range(0.0..1.0) < rate {
+            self.size * (1.0 + rng.gen_range(-ORGAN_SIZE_STEP..=ORGAN_SIZE_STEP))
+        } else {
+            self.size
+        };
+
+        Self {
+            size,
+            ..self.clone()
+        }
+    }
+
+    fn lerp(a: &Self, b: &Self, t: f32) -> Self {
+        let base = if t < 0.5 { a } else { b };
+
+        Self {
+            size: a.size + (b.size - a.size) * t.clamp(0.0, 1.0),
+            ..base.clone()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::SmallRng;
+
+    fn curve(keys: &[(f32, f32)]) -> Param {
+        Param::Curve(Curve {
+            along: CurveAxis::Depth,
+            interpolation: Interpolation::Linear,
+            keys: keys.to_vec(),
+        })
+    }
+
+    #[test]
+    fn lerp_stays_between_its_values() {
+        let gene = Continuous::new(0.0, 1.0);
+
+        assert_eq!(gene.lerp(0.25, 0.75, 0.5), 0.5);
+        assert_eq!(gene.lerp(0.25, 0.75, 2.0), 0.75);
+        assert_eq!(gene.lerp(0.5, 3.0, 1.0), 3.0);
+        assert_eq!(gene.lerp(-2.0, -2.0, 0.5), -2.0);
+
+        let gene = Discrete::new(2, 8);
+
+        assert_eq!(gene.lerp(2, 5, 0.5), 4);
+        assert_eq!(gene.lerp(2, 5, -1.0), 2);
+        assert_eq!(gene.lerp(0, 20, 1.0), 20);
+        assert_eq!(gene.lerp(12, 12, 0.5), 12);
+    }
+
+    #[test]
+    fn mutate_stays_in_range() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let continuous = Continuous::new(0.0, 1.0);
+        let discrete = Discrete::new(2, 4);
+
+        for _ in 0..1000 {
+            assert!((0.0..=1.0).contains(&continuous.mutate(1.0, 1.0, &mut rng)));
+            assert!((2..=4).contains(&discrete.mutate(4, 1.0, &mut rng)));
+            assert!((0.0..=3.0).contains(&continuous.mutate(3.0, 1.0, &mut rng)));
+            assert!((2..=9).contains(&discrete.mutate(9, 1.0, &mut rng)));
+        }
+
+        assert_eq!(continuous.mutate(0.5, 0.0, &mut rng), 0.5);
+        assert_eq!(discrete.mutate(3, 0.0, &mut rng), 3);
+    }
+
+    #[test]
+    fn lerp_param_blends_matching_curves_key_by_key() {
+        let gene = Continuous::new(0.0, 10.0);
+        let a = curve(&[(0.0, 0.0), (1.0, 2.0)]);
+        let b = curve(&[(0.0, 4.0), (1.0, 6.0)]);
+
+        assert_eq!(
+            gene.lerp_param(&a, &b, 0.5),
+            curve(&[(0.0, 2.0), (1.0, 4.0)])
+        );
+        assert_eq!(
+            gene.lerp_param(&a, &Param::Constant(4.0), 0.5),
+            curve(&[(0.0, 2.0), (1.0, 3.0)])
+        );
+        assert_eq!(
+            gene.lerp_param(&Param::Constant(4.0), &a, 0.5),
+            curve(&[(0.0, 2.0), (1.0, 3.0)])
+        );
+    }
+
+    #[test]
+    fn lerp_param_picks_a_side_for_curves_that_dont_line_up() {
+        let gene = Continuous::new(0.0, 10.0);
+        let a = curve(&[(0.0, 0.0), (1.0, 2.0)]);
+        let b = curve(&[(0.5, 4.0)]);
+
+        assert_eq!(gene.lerp_param(&a, &b, 0.25), a);
+        assert_eq!(gene.lerp_param(&a, &b, 0.75), b);
+    }
+
+    #[test]
+    fn vec_lerp_keeps_the_tail_of_the_nearer_parent() {
+        let a = vec![Param::Constant(0.0)];
+        let b = vec![Param::Constant(1.0), Param::Constant(2.0)];
+
+        assert_eq!(Genetic::lerp(&a, &b, 0.25).len(), 1);
+        assert_eq!(Genetic::lerp(&a, &b, 0.75).len(), 2);
+    }
+
+    #[test]
+    fn genomes_lerp_between_their_parents() {
+        let a = Genome::test();
+        let b = Genome {
+            max_splits: 7,
+            branch_length: 2.5,
+            seed: Some(1),
+            ..Genome::test()
+        };
+
+        let start = Genome::lerp(&a, &b, 0.0).unwrap();
+        assert_eq!(start.max_splits, a.max_splits);
+        assert_eq!(start.seed, None);
+
+        let middle = Genome::lerp(&a, &b, 0.5).unwrap();
+        assert_eq!(middle.max_splits, 6);
+        assert_eq!(middle.branch_length, 2.0);
+        assert_eq!(middle.seed, Some(1));
+    }
+
+    #[test]
+    fn blending_a_genome_with_itself_keeps_it() {
+        let genome = Genome {
+            starting_radius: 3.0,
+            branch_length: 12.0,
+            max_splits: 10,
+            ..Genome::test()
+        };
+        let blend = Genome::lerp(&genome, &genome, 0.3).unwrap();
+
+        assert_eq!(blend.starting_radius, 3.0);
+        assert_eq!(blend.branch_length, 12.0);
+        assert_eq!(blend.max_splits, 10);
+    }
+
+    #[test]
+    fn bred_genomes_are_valid() {
+        let mut rng = SmallRng::seed_from_u64(0);
+        let a = Genome::test();
+        let b = Genome {
+            radial_segments: 5,
+            ..Genome::test()
+        };
+
+        for _ in 0..100 {
+            let child = Genome::crossover(&a, &b, &mut rng).unwrap();
+            let child = child.mutate(1.0, &mut rng).unwrap();
+
+            assert_eq!(child.validate(), Ok(()));
+            assert!(child.patch.is_none());
+        }
+    }
+
+    #[test]
+    fn left_out_level_values_blend_whole() {
+        let a = LevelParams {
+            length: Some(1.0),
+            radial_segments: Some(8),
+            ..Default::default()
+        };
+        let b = LevelParams {
+            length: Some(3.0),
+            ..Default::default()
+        };
+
+        let blend = LevelParams::lerp(&a, &b, 0.25);
+        assert_eq!(blend.length, Some(1.5));
+        assert_eq!(blend.radial_segments, Some(8));
+
+        let blend = LevelParams::lerp(&a, &b, 0.75);
+        assert_eq!(blend.length, Some(2.5));
+        assert_eq!(blend.radial_segments, None);
+    }
+
+    #[test]
+    fn blended_light_is_a_direction() {
+        let tropism = |light: Vec3| Tropism {
+            light: Some(light),
+            ..Default::default()
+        };
+        let a = tropism(Vec3::new(2.0, 0.0, 0.0));
+        let b = tropism(Vec3::new(0.0, 0.0, 0.5));
+
+        let light = Tropism::lerp(&a, &b, 0.5).light.unwrap();
+        assert!((light.length() - 1.0).abs() < 0.0001);
+        assert!((light.x - light.z).abs() < 0.0001);
+
+        let light = Tropism::lerp(&a, &tropism(Vec3::new(-1.0, 0.0, 0.0)), 0.5).light;
+        assert_eq!(light, Some(Vec3::new(-1.0, 0.0, 0.0)));
+    }
+
+    #[test]
+    fn pinned_seeds_mutate_but_unpinned_ones_stay_unpinned() {
+        let mut rng = SmallRng::seed_from_u64(0);
+
+        assert_eq!(SeedGene.mutate(&None, 1.0, &mut rng), None);
+        assert_ne!(SeedGene.mutate(&Some(1), 1.0, &mut rng), Some(1));
+        assert_eq!(SeedGene.mutate(&Some(1), 0.0, &mut rng), Some(1));
+    }
+}
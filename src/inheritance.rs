@@ -0,0 +1,299 @@
+use crate::colonization::*;
+use crate::curve::*;
+use crate::envelope::*;
+use crate::level::*;
+use crate::organ::*;
+use crate::phyllotaxis::*;
+use crate::plant::{Genome, GenomeError};
+use crate::roots::*;
+use crate::skeleton::*;
+use crate::tropism::*;
+use bevy::{asset::HandleId, prelude::*, utils::HashMap};
+use serde::{Deserialize, Deserializer};
+use std::path::{Path, PathBuf};
+
+macro_rules! genome_patch {
+    ($($field:ident: $ty:ty, $kind:ident, $gene:expr;)*) => {
+        /// The fields a `.gno` file sets, every field left out is taken from the genome
+        /// it `extends`.
+        ///
+        /// ```ron
+        /// (
+        ///     extends: "plants/base.gno",
+        ///     leaf_density: 20.0,
+        /// )
+        /// ```
+        #[derive(Deserialize, Clone, Default)]
+        #[serde(default)]
+        pub struct GenomePatch {
+            /// Asset path of the genome this one is based on.
+            #[serde(deserialize_with = "present")]
+            pub extends: Option<String>,
+            $(
+                #[serde(deserialize_with = "present")]
+                pub $field: Option<$ty>,
+            )*
+        }
+
+        impl GenomePatch {
+            /// Fills every field this patch leaves out from `parent`, the result extends
+            /// whatever `parent` extends.
+            pub fn over(self, parent: GenomePatch) -> GenomePatch {
+                GenomePatch {
+                    extends: parent.extends,
+                    $($field: self.$field.or(parent.$field),)*
+                }
+            }
+
+            /// Builds and validates the genome, once every genome this patch extends has
+            /// been applied with [`GenomePatch::over`].
+            pub fn into_genome(self) -> Result<Genome, GenomeError> {
+                let genome = Genome {
+                    $($field: field!($kind, $field, self.$field),)*
+                    patch: None,
+                };
+
+                genome.validate()?;
+
+                Ok(genome)
+            }
+        }
+
+        impl From<Genome> for GenomePatch {
+            fn from(genome: Genome) -> Self {
+                Self {
+                    extends: None,
+                    $($field: Some(genome.$field),)*
+                }
+            }
+        }
+    };
+}
+
+/// A field of the genome a patch resolves to, either one every genome has to set or
+/// one that's left to its default.
+macro_rules! field {
+    (required, $field:ident, $value:expr) => {
+        required(stringify!($field), $value)?
+    };
+    (default, $field:ident, $value:expr) => {
+        $value.unwrap_or_default()
+    };
+}
+
+crate::plant::genome_fields!(genome_patch);
+
+/// Deserializes a field that is present in the file without wrapping it in `Some`,
+/// so `seed: None` overrides the parent's seed instead of reading as a missing field.
+pub(crate) fn present<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: Deserialize<'de>,
+{
+    T::deserialize(deserializer).map(Some)
+}
+
+fn required<T>(field: &'static str, value: Option<T>) -> Result<T, GenomeError> {
+    value.ok_or(GenomeError::Missing { field })
+}
+
+impl GenomePatch {
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, anyhow::Error> {
+        Ok(ron::de::from_bytes(bytes)?)
+    }
+}
+
+/// Keeps track of the genomes visited while resolving `extends`, so a genome that
+/// ends up extending itself is an error instead of a hang.
+#[derive(Default)]
+pub struct ExtendsChain {
+    visited: Vec<PathBuf>,
+}
+
+impl ExtendsChain {
+    pub fn visit(&mut self, path: &Path) -> Result<(), anyhow::Error> {
+        if self.visited.iter().any(|visited| visited == path) {
+            anyhow::bail!("'{}' ends up extending itself", path.display());
+        }
+
+        self.visited.push(path.to_owned());
+
+        Ok(())
+    }
+}
+
+/// Strong handles to the genome every loaded genome `extends`, keyed by the genome
+/// extending it.
+///
+/// The loader only makes sure a parent is loaded, so a parent no plant grows from
+/// would be freed, and edits to it come back as a new asset instead of a change.
+#[derive(Default)]
+pub struct GenomeParents(HashMap<HandleId, Handle<Genome>>);
+
+/// Re-applies the own fields of every genome extending a genome that changed, which
+/// marks them modified in turn so plants regrow and deeper descendants follow.
+///
+/// The asset server only reloads the file that changed on disk, not the files
+/// depending on it. A parent that's loaded after the genomes extending it counts as a
+/// change too, genomes it doesn't actually change are left alone.
+pub fn genome_inheritance_system(
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<Genome>>,
+    mut genomes: ResMut<Assets<Genome>>,
+    mut parents: ResMut<GenomeParents>,
+) {
+    let mut changed = Vec::new();
+
+    for event in events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                let extends = genomes
+                    .get(handle)
+                    .and_then(|genome| genome.patch.as_ref())
+                    .and_then(|patch| patch.extends.as_ref());
+
+                if let Some(extends) = extends {
+                    parents
+                        .0
+                        .insert(handle.id, asset_server.load(extends.as_str()));
+                }
+
+                changed.push(handle.clone_weak());
+            }
+            AssetEvent::Removed { handle } => {
+                parents.0.remove(&handle.id);
+            }
+        }
+    }
+
+    for parent_handle in changed {
+        let parent_path = match asset_server.get_handle_path(&parent_handle) {
+            Some(path) => path.path().to_owned(),
+            None => continue,
+        };
+
+        let parent = match genomes.get(&parent_handle) {
+            Some(parent) => parent.clone(),
+            None => continue,
+        };
+
+        let children = genomes
+            .iter()
+            .filter(|(_, genome)| {
+                genome
+                    .patch
+                    .as_ref()
+                    .and_then(|patch| patch.extends.as_ref())
+                    .is_some_and(|extends| Path::new(extends) == parent_path)
+            })
+            .map(|(id, _)| id)
+            .collect::<Vec<_>>();
+
+        for id in children {
+            let genome = genomes.get(id).unwrap();
+            let patch = genome.patch.clone().unwrap();
+
+            match patch.clone().over(parent.clone().into()).into_genome() {
+                // getting the genome mutably marks it modified, which regrows its plants
+                Ok(resolved) if !same_genome(&resolved, genome) => {
+                    let genome = genomes.get_mut(id).unwrap();
+
+                    *genome = resolved;
+                    genome.patch = Some(patch);
+                }
+                Ok(_) => {}
+                Err(e) => error!("genome extending '{}': {}", parent_path.display(), e),
+            }
+        }
+    }
+}
+
+/// Whether two genomes grow the same plants, compared by what they'd be saved as.
+fn same_genome(a: &Genome, b: &Genome) -> bool {
+    match (ron::ser::to_string(a), ron::ser::to_string(b)) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn patch(source: &str) -> GenomePatch {
+        GenomePatch::from_bytes(source.as_bytes()).unwrap()
+    }
+
+    fn resolve(patch: GenomePatch, parent: Genome) -> Genome {
+        patch
+            .over(parent.into())
+            .into_genome()
+            .unwrap_or_else(|e| panic!("{}", e))
+    }
+
+    #[test]
+    fn genomes_extending_themselves_are_refused() {
+        let mut chain = ExtendsChain::default();
+
+        chain.visit("plants/a.gno".as_ref()).unwrap();
+        chain.visit("plants/b.gno".as_ref()).unwrap();
+
+        assert_eq!(
+            chain
+                .visit("plants/a.gno".as_ref())
+                .unwrap_err()
+                .to_string(),
+            "'plants/a.gno' ends up extending itself"
+        );
+    }
+
+    #[test]
+    fn patches_keep_their_own_fields_over_the_parent() {
+        let mut parent = Genome::test();
+        parent.seed = Some(3);
+
+        let genome = resolve(
+            patch(r#"(extends: "plants/test.gno", leaf_size: 0.5, seed: None)"#),
+            parent.clone(),
+        );
+
+        assert_eq!(genome.leaf_size, 0.5);
+        // a field set to `None` overrides the parent instead of being left out
+        assert_eq!(genome.seed, None);
+        assert_eq!(genome.max_splits, parent.max_splits);
+    }
+
+    #[test]
+    fn patches_extend_whatever_their_parent_extends() {
+        let resolved = patch(r#"(extends: "plants/b.gno", leaf_size: 0.5)"#).over(patch(
+            r#"(extends: "plants/a.gno", leaf_size: 0.3, max_splits: 4)"#,
+        ));
+
+        assert_eq!(resolved.extends.as_deref(), Some("plants/a.gno"));
+        assert_eq!(resolved.leaf_size, Some(0.5));
+        assert_eq!(resolved.max_splits, Some(4));
+        assert_eq!(
+            resolved.into_genome().err(),
+            Some(GenomeError::Missing {
+                field: "branches_per_split"
+            })
+        );
+    }
+
+    #[test]
+    fn patches_follow_changes_to_their_parent() {
+        let own = patch("(leaf_size: 0.5)");
+        let mut parent = Genome::test();
+        let before = resolve(own.clone(), parent.clone());
+
+        parent.max_splits += 1;
+        parent.leaf_size = 2.0;
+
+        let after = resolve(own, parent);
+
+        assert_eq!(after.max_splits, before.max_splits + 1);
+        assert_eq!(after.leaf_size, 0.5);
+        assert!(!same_genome(&before, &after));
+        assert!(same_genome(&after, &after.clone()));
+    }
+}
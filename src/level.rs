@@ -0,0 +1,219 @@
+use crate::curve::*;
+use crate::inheritance::present;
+use crate::plant::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Overrides for the branches at one split depth, every field left out falls back to
+/// the genome-wide value.
+///
+/// ```ron
+/// levels: [
+///     (),
+///     (bend: 0.3, length: 2.0),
+///     (radial_segments: 8, leaf_size: 0.3),
+/// ],
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default)]
+#[serde(default)]
+pub struct LevelParams {
+    /// How many branches split off the end of a branch at this level.
+    #[serde(deserialize_with = "present")]
+    pub branches: Option<Range<usize>>,
+    #[serde(deserialize_with = "present")]
+    pub length: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub bend: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub twist: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub radial_segments: Option<usize>,
+    #[serde(deserialize_with = "present")]
+    pub leaf_density: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub leaf_size: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub leaf_length: Option<f32>,
+    #[serde(deserialize_with = "present")]
+    pub leaf_offset: Option<f32>,
+}
+
+impl LevelParams {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        if let Some(branches) = &self.branches {
+            if branches.start > branches.end {
+                return Err(GenomeError::EmptyRange {
+                    field: "branches",
+                    start: branches.start,
+                    end: branches.end,
+                });
+            }
+        }
+
+        let optional = |field, value: Option<f32>, check: fn(&'static str, f32) -> _| {
+            value.map_or(Ok(()), |value| check(field, value))
+        };
+
+        optional("length", self.length, check_non_negative)?;
+        optional("bend", self.bend, check_positive)?;
+        optional("twist", self.twist, check_non_negative)?;
+        optional("leaf_density", self.leaf_density, check_non_negative)?;
+        optional("leaf_size", self.leaf_size, check_non_negative)?;
+        optional("leaf_length", self.leaf_length, check_non_negative)?;
+        optional("leaf_offset", self.leaf_offset, check_positive)?;
+
+        if let Some(radial_segments) = self.radial_segments {
+            check_count("radial_segments", radial_segments, 3)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// The parameters of the branches at one split depth and height, with the
+/// genome-wide values filled in.
+#[derive(Clone, Debug)]
+pub struct Level {
+    pub branches: Range<usize>,
+    pub length: f32,
+    pub bend: f32,
+    pub twist: f32,
+    pub radial_segments: usize,
+    pub leaf_density: f32,
+    pub leaf_size: f32,
+    pub leaf_length: f32,
+    pub leaf_offset: f32,
+}
+
+impl Genome {
+    /// `radial_segments` of the branches at `split` for genomes without `levels`, half
+    /// of an even count from split `thin` on.
+    pub(crate) fn unlevelled_radial_segments(&self, split: usize, thin: usize) -> usize {
+        if split >= thin && self.radial_segments.is_multiple_of(2) {
+            self.radial_segments / 2
+        } else {
+            self.radial_segments
+        }
+    }
+
+    /// Branches deeper than the `levels` list use its last entry.
+    pub fn level(&self, split: usize, height: f32) -> Level {
+        let position = CurvePosition::new(self, split, height);
+        let params = self
+            .levels
+            .get(split)
+            .or_else(|| self.levels.last())
+            .cloned()
+            .unwrap_or_default();
+
+        Level {
+            branches: params
+                .branches
+                .unwrap_or_else(|| self.branches_per_split.clone()),
+            length: params.length.unwrap_or(self.branch_length),
+            bend: params
+                .bend
+                .unwrap_or_else(|| self.branch_bend.sample(position)),
+            twist: params.twist.unwrap_or(self.branch_twist),
+            radial_segments: params.radial_segments.unwrap_or_else(|| {
+                if self.levels.is_empty() {
+                    self.unlevelled_radial_segments(split, 2)
+                } else {
+                    self.radial_segments
+                }
+            }),
+            leaf_density: params
+                .leaf_density
+                .unwrap_or_else(|| self.leaf_density.sample(position)),
+            leaf_size: params.leaf_size.unwrap_or(self.leaf_size),
+            leaf_length: params.leaf_length.unwrap_or(self.leaf_length),
+            leaf_offset: params.leaf_offset.unwrap_or(self.leaf_offset),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn levels_override_the_genome() {
+        let genome = Genome {
+            levels: vec![
+                LevelParams {
+                    branches: Some(1..2),
+                    bend: Some(0.3),
+                    ..Default::default()
+                },
+                LevelParams {
+                    length: Some(2.0),
+                    ..Default::default()
+                },
+            ],
+            ..Genome::test()
+        };
+
+        let trunk = genome.level(0, 0.0);
+        assert_eq!(trunk.branches, 1..2);
+        assert_eq!(trunk.bend, 0.3);
+        assert_eq!(trunk.length, genome.branch_length);
+
+        let branch = genome.level(1, 0.0);
+        assert_eq!(branch.branches, genome.branches_per_split);
+        assert_eq!(branch.bend, 0.65);
+        assert_eq!(branch.length, 2.0);
+    }
+
+    #[test]
+    fn deeper_splits_use_the_last_level() {
+        let genome = Genome::test();
+
+        assert_eq!(genome.level(1, 0.0).radial_segments, 16);
+        assert_eq!(genome.level(2, 0.0).radial_segments, 8);
+        assert_eq!(genome.level(4, 0.0).radial_segments, 8);
+    }
+
+    #[test]
+    fn unlevelled_genomes_halve_even_radial_segments() {
+        let genome = Genome {
+            levels: Vec::new(),
+            ..Genome::test()
+        };
+
+        assert_eq!(genome.level(1, 0.0).radial_segments, 16);
+        assert_eq!(genome.level(2, 0.0).radial_segments, 8);
+        assert_eq!(genome.level(4, 0.0).radial_segments, 8);
+
+        let genome = Genome {
+            radial_segments: 7,
+            ..genome
+        };
+
+        assert_eq!(genome.level(4, 0.0).radial_segments, 7);
+    }
+
+    #[test]
+    fn validate_names_the_field() {
+        let params = LevelParams {
+            branches: Some(Range { start: 3, end: 1 }),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            params.validate(),
+            Err(GenomeError::EmptyRange {
+                field: "branches",
+                start: 3,
+                end: 1,
+            })
+        );
+
+        let params = LevelParams {
+            radial_segments: Some(2),
+            ..Default::default()
+        };
+
+        assert!(params.validate().is_err());
+        assert_eq!(LevelParams::default().validate(), Ok(()));
+    }
+}
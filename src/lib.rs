@@ -0,0 +1,23 @@
+pub mod colonization;
+pub mod curve;
+pub mod envelope;
+pub mod export;
+pub mod genetics;
+pub mod inheritance;
+pub mod level;
+pub mod lsystem;
+pub mod metrics;
+pub mod occlusion;
+pub mod organ;
+pub mod phyllotaxis;
+pub mod plant;
+pub mod ron_loader;
+pub mod roots;
+pub mod shader_loader;
+pub mod shadow_render_resources;
+pub mod skeleton;
+pub mod sky;
+pub mod sun;
+pub mod terrain;
+pub mod tropism;
+pub mod wind;
@@ -0,0 +1,330 @@
+use crate::plant::{
+    check_count, check_non_negative, check_positive, GenomeError, Leaf, PlantGenerator,
+};
+use crate::skeleton::*;
+use bevy::{prelude::*, reflect::TypeUuid};
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// Derivation stops early, at the last full iteration, once the string would grow
+/// past this many symbols.
+const MAX_SYMBOLS: usize = 1_000_000;
+
+fn one() -> f32 {
+    1.0
+}
+
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Production {
+    pub symbol: char,
+    pub successor: String,
+    #[serde(default = "one")]
+    pub probability: f32,
+}
+
+/// How the derived string is drawn.
+///
+/// * `F` moves forward one `step`, adding a segment to the current branch
+/// * `+` `-` turn, `&` `^` pitch and `\` `/` roll by `angle` degrees, `|` turns around
+/// * `[` `]` push and pop the turtle state, the first `F` after a `[` starts a new branch
+/// * `!` multiplies the radius by `radius_decay`, `L` places a leaf
+///
+/// Every other symbol is only used during rewriting.
+#[derive(Serialize, Deserialize, Clone)]
+pub struct Turtle {
+    pub step: f32,
+    pub angle: f32,
+    pub radius: f32,
+    pub radius_decay: f32,
+    pub radial_segments: usize,
+    pub leaf_size: f32,
+    pub leaf_length: f32,
+}
+
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
+#[uuid = "0f5b1a8e-3c6d-4f0e-9d4b-7a2e58c1b6d3"]
+pub struct LSystem {
+    pub seed: Option<u64>,
+    pub axiom: String,
+    pub iterations: usize,
+    pub rules: Vec<Production>,
+    pub turtle: Turtle,
+    #[serde(default)]
+    pub growth: Growth,
+}
+
+impl Turtle {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        check_positive("turtle.step", self.step)?;
+        check_positive("turtle.angle", self.angle)?;
+        check_positive("turtle.radius", self.radius)?;
+        check_positive("turtle.radius_decay", self.radius_decay)?;
+        check_count("turtle.radial_segments", self.radial_segments, 3)?;
+        check_non_negative("turtle.leaf_size", self.leaf_size)?;
+        check_non_negative("turtle.leaf_length", self.leaf_length)?;
+
+        Ok(())
+    }
+}
+
+impl LSystem {
+    /// Checks the turtle and rules like [`Genome::validate`](crate::plant::Genome::validate),
+    /// so a bad `.lsys` file is rejected on load instead of growing a broken plant.
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        for production in &self.rules {
+            check_non_negative("rules.probability", production.probability)?;
+        }
+
+        self.turtle.validate()?;
+        self.growth.validate()?;
+
+        Ok(())
+    }
+
+    pub fn derive(&self, rng: &mut impl Rng) -> String {
+        let mut string = self.axiom.clone();
+
+        for _ in 0..self.iterations {
+            let mut next = String::with_capacity(string.len() * 2);
+
+            for symbol in string.chars() {
+                match self.choose(symbol, rng) {
+                    Some(production) => next.push_str(&production.successor),
+                    None => next.push(symbol),
+                }
+
+                // checked as the string grows, a long successor over a long string
+                // would take up far more memory than the limit by the end of it
+                if next.len() > MAX_SYMBOLS {
+                    return string;
+                }
+            }
+
+            string = next;
+        }
+
+        string
+    }
+
+    fn choose(&self, symbol: char, rng: &mut impl Rng) -> Option<&Production> {
+        let candidates = self.rules.iter().filter(|p| p.symbol == symbol);
+        let total: f32 = candidates.clone().map(|p| p.probability.max(0.0)).sum();
+
+        if total <= 0.0 {
+            return None;
+        }
+
+        let mut pick = rng.gen_range(0.0..total);
+
+        for production in candidates {
+            let probability = production.probability.max(0.0);
+
+            if pick < probability {
+                return Some(production);
+            }
+
+            pick -= probability;
+        }
+
+        None
+    }
+}
+
+#[derive(Clone, Copy)]
+struct TurtleState {
+    position: Vec3,
+    rotation: Quat,
+    radius: f32,
+    distance: f32,
+    branch: Option<usize>,
+    depth: usize,
+    new_branch: bool,
+}
+
+impl PlantGenerator for LSystem {
+    fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    fn growth(&self) -> Growth {
+        self.growth
+    }
+
+    fn generate_skeleton(&self, seed: u64, _light: Vec3) -> PlantSkeleton {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        let string = self.derive(&mut rng);
+
+        let turtle = &self.turtle;
+        let angle = turtle.angle.to_radians();
+        let radial_segments = turtle.radial_segments.max(3);
+
+        let mut skeleton = PlantSkeleton::default();
+        let mut stack = Vec::new();
+        let mut state = TurtleState {
+            position: Vec3::ZERO,
+            rotation: Quat::IDENTITY,
+            radius: turtle.radius,
+            distance: 0.0,
+            branch: None,
+            depth: 0,
+            new_branch: true,
+        };
+
+        for symbol in string.chars() {
+            match symbol {
+                'F' => {
+                    let start = SegmentFrame {
+                        position: state.position,
+                        rotation: state.rotation,
+                        radius: state.radius,
+                        sway: state.distance,
+                    };
+
+                    state.position += state.rotation * Vec3::Y * turtle.step;
+                    state.distance += turtle.step;
+
+                    let frame = SegmentFrame {
+                        position: state.position,
+                        rotation: state.rotation,
+                        radius: state.radius,
+                        sway: state.distance,
+                    };
+
+                    match state.branch {
+                        Some(index) if !state.new_branch => {
+                            let branch = &mut skeleton.branches[index];
+
+                            branch.frames.push(frame);
+                            branch.end_radius = frame.radius;
+                            branch.length += turtle.step;
+                        }
+                        parent => {
+                            let parent_frame = parent
+                                .map(|p| skeleton.branches[p].frames.len() - 1)
+                                .unwrap_or(0);
+
+                            let index = skeleton.add_branch(SkeletonBranch {
+                                parent,
+                                parent_frame,
+                                children: Vec::new(),
+                                split: state.depth,
+                                start: start.position,
+                                direction: start.rotation * Vec3::Y,
+                                bend: Vec3::ZERO,
+                                start_radius: start.radius,
+                                end_radius: frame.radius,
+                                length: turtle.step,
+                                radial_segments,
+                                sway: start.sway,
+                                frames: vec![start, frame],
+                                leaves: Vec::new(),
+                                organs: Vec::new(),
+                                root: false,
+                            });
+
+                            state.branch = Some(index);
+                            state.new_branch = false;
+                        }
+                    }
+                }
+                '+' => state.rotation *= Quat::from_rotation_z(angle),
+                '-' => state.rotation *= Quat::from_rotation_z(-angle),
+                '&' => state.rotation *= Quat::from_rotation_x(angle),
+                '^' => state.rotation *= Quat::from_rotation_x(-angle),
+                '\\' => state.rotation *= Quat::from_rotation_y(angle),
+                '/' => state.rotation *= Quat::from_rotation_y(-angle),
+                '|' => state.rotation *= Quat::from_rotation_z(std::f32::consts::PI),
+                '!' => state.radius *= turtle.radius_decay,
+                '[' => {
+                    stack.push(state);
+
+                    state.depth += 1;
+                    state.new_branch = true;
+                }
+                ']' => {
+                    if let Some(popped) = stack.pop() {
+                        state = popped;
+                    }
+                }
+                'L' => {
+                    if let Some(index) = state.branch {
+                        skeleton.branches[index].leaves.push(Leaf {
+                            pos: state.position,
+                            rot: state.rotation
+                                * Quat::from_rotation_x(-std::f32::consts::FRAC_PI_2),
+                            sway: state.distance,
+                            size: turtle.leaf_size,
+                            length: turtle.leaf_length,
+                        });
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        skeleton
+    }
+}
+
+pub struct LSystemLoader;
+
+crate::ron_loader!(LSystemLoader, "lsys" => LSystem);
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lsystem() -> LSystem {
+        ron::from_str(include_str!("../assets/plants/conifer.lsys")).unwrap()
+    }
+
+    #[test]
+    fn derive_rewrites_every_symbol() {
+        let lsystem = LSystem {
+            axiom: "AB".into(),
+            iterations: 2,
+            rules: ron::from_str(
+                "[(symbol: 'A', successor: \"AB\"), (symbol: 'B', successor: \"A\")]",
+            )
+            .unwrap(),
+            ..lsystem()
+        };
+
+        assert_eq!(lsystem.derive(&mut thread_rng()), "ABAAB");
+    }
+
+    #[test]
+    fn derive_stops_at_the_last_string_under_the_limit() {
+        let lsystem = LSystem {
+            axiom: "A".into(),
+            iterations: 10,
+            rules: vec![Production {
+                symbol: 'A',
+                successor: "A".repeat(1000),
+                probability: 1.0,
+            }],
+            ..lsystem()
+        };
+
+        assert_eq!(lsystem.derive(&mut thread_rng()).len(), 1_000_000);
+    }
+
+    #[test]
+    fn turtle_leaves_may_be_left_out() {
+        let mut lsystem = lsystem();
+
+        lsystem.turtle.leaf_size = 0.0;
+        assert_eq!(lsystem.validate(), Ok(()));
+
+        lsystem.turtle.leaf_size = -1.0;
+        assert_eq!(
+            lsystem.validate(),
+            Err(GenomeError::OutOfRange {
+                field: "turtle.leaf_size",
+                value: -1.0,
+                min: 0.0,
+                max: f64::INFINITY,
+            })
+        );
+    }
+}
@@ -1,16 +1,14 @@
-mod plant;
-mod ron_loader;
-mod shadow_render_resources;
-mod sky;
-mod sun;
-mod terrain;
-
-use bevy::prelude::*;
+use bevy::{ecs::system::SystemParam, prelude::*};
+use futures_lite::future;
 use rand::prelude::*;
+use tree::{
+    export, lsystem, metrics, occlusion, organ, plant, shader_loader, sky, sun, terrain, wind,
+};
 
 fn main() {
     App::build()
         .insert_resource(ClearColor(Color::rgba(0.0, 0.0, 0.0, 0.0)))
+        .insert_resource(plant::WorldSeed::from_env())
         .insert_resource(WindowDescriptor {
             vsync: false,
             ..Default::default()
@@ -18,21 +16,40 @@ fn main() {
         // plugins
         .add_plugins(sky::Plugins)
         .add_plugin(sun::SunPlugin)
+        .add_plugin(wind::WindPlugin)
+        .add_plugin(shader_loader::ShaderIncludePlugin)
         .add_plugin(plant::PlantPlugin)
+        .add_plugin(metrics::PlantDiagnosticsPlugin)
         // startup systems
         .add_startup_system(setup.system())
         .add_startup_system(bevy_mod_debugdump::print_render_graph.system())
         // system
         .add_system(character_system.system())
         .add_system(cursor_grab_system.system())
-        .add_system(plant_mesh_system.system())
+        .add_system(plant_reload_system::<plant::Genome>.system())
+        .add_system(plant_reload_system::<lsystem::LSystem>.system())
+        .add_system(season_system.system())
+        .add_system(plant_season_system.system())
+        .add_system(plant_mesh_system::<plant::Genome>.system())
+        .add_system(plant_mesh_system::<lsystem::LSystem>.system())
+        .add_system(plant_lod_system.system())
+        .add_system(plant_export_system.system())
+        .add_system(plant_task_system.system())
         .add_system(plant_growth_system.system())
+        .add_system(plant_growth_task_system.system())
         .add_system(terrain::terrain_system.system())
         // run
         .run();
 }
 
-fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server: Res<AssetServer>) {
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    asset_server: Res<AssetServer>,
+    world_seed: Res<plant::WorldSeed>,
+) {
+    info!("world seed: {}", world_seed.0);
+
     let player = commands
         .spawn()
         .insert(Player {})
@@ -60,7 +77,7 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server:
         .insert(Parent(player))
         .id();
 
-    let mut rng = thread_rng();
+    let mut rng = rand::rngs::SmallRng::seed_from_u64(world_seed.0);
 
     const SPREAD: f32 = 4.0;
 
@@ -77,6 +94,8 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server:
                     material: plant::PlantMaterial::new(
                         asset_server.load("textures/bark.png"),
                         asset_server.load("textures/leaf_front.png"),
+                        asset_server.load("textures/blossom.png"),
+                        asset_server.load("textures/fruit.png"),
                     ),
                     transform,
                     ..Default::default()
@@ -85,6 +104,24 @@ fn setup(mut commands: Commands, mut meshes: ResMut<Assets<Mesh>>, asset_server:
         }
     }
 
+    for x in -1..=1 {
+        let x = x as f32 * SPREAD * 2.0 + rng.gen_range(-1.0..1.0);
+        let z = -SPREAD * 4.0 + rng.gen_range(-1.0..1.0);
+
+        commands
+            .spawn_bundle(plant::PlantBundle {
+                material: plant::PlantMaterial::new(
+                    asset_server.load("textures/bark.png"),
+                    asset_server.load("textures/leaf_front.png"),
+                    asset_server.load("textures/blossom.png"),
+                    asset_server.load("textures/fruit.png"),
+                ),
+                transform: Transform::from_translation(Vec3::new(x, -0.1, z)),
+                ..Default::default()
+            })
+            .insert(asset_server.load::<lsystem::LSystem, _>("plants/conifer.lsys"));
+    }
+
     commands.spawn_bundle(MeshBundle {
         mesh: bevy::sprite::QUAD_HANDLE.typed(),
         render_pipelines: RenderPipelines::from_pipelines(vec![
@@ -133,23 +170,400 @@ impl PlayerCamera {
     }
 }
 
-pub fn plant_growth_system(time: Res<Time>, mut query: Query<&mut plant::PlantMaterial>) {
-    for mut plant_material in query.iter_mut() {
-        plant_material.growth += time.delta_seconds() * 0.5;
+/// Ages every plant and re-meshes all of its levels of detail on the task pool as it
+/// grows, one age at a time.
+pub fn plant_growth_system(
+    mut commands: Commands,
+    time: Res<Time>,
+    task_pool: Res<bevy::tasks::AsyncComputeTaskPool>,
+    mut query: Query<(
+        Entity,
+        &mut plant::PlantAge,
+        &mut plant::PlantGrowth,
+        &plant::PlantLod,
+        Option<&plant::PlantGrowthTask>,
+    )>,
+) {
+    for (entity, mut age, mut growth, lod, task) in query.iter_mut() {
+        if age.0 < growth.growth.max_age {
+            age.0 = (age.0 + time.delta_seconds()).min(growth.growth.max_age);
+        }
+
+        if task.is_some() || !growth.needs_mesh(age.0) {
+            continue;
+        }
+
+        let task = growth.spawn_meshes(&task_pool, age.0, lod.meshes.len());
+
+        commands.entity(entity).insert(plant::PlantGrowthTask(task));
     }
 }
 
-pub fn plant_mesh_system(
+/// A growing plant along with its pending meshes and the meshes they replace.
+type GrowingPlantMeshes = (
+    Entity,
+    &'static mut plant::PlantGrowthTask,
+    &'static plant::PlantGrowth,
+    &'static plant::PlantInstance,
+    &'static mut plant::PlantLod,
+    &'static mut Handle<Mesh>,
+    &'static mut metrics::PlantMetrics,
+);
+
+/// Swaps in the meshes of finished [`plant::PlantGrowthTask`]s, plants that are
+/// fully grown switch to the shared meshes in the [`plant::PlantMeshCache`].
+pub fn plant_growth_task_system(
     mut commands: Commands,
     mut meshes: ResMut<Assets<Mesh>>,
-    gnomes: Res<Assets<plant::Genome>>,
-    query: Query<(Entity, &Handle<plant::Genome>), Without<Handle<Mesh>>>,
+    mut cache: ResMut<plant::PlantMeshCache>,
+    mut query: Query<GrowingPlantMeshes>,
 ) {
-    for (entity, genome_handle) in query.iter() {
+    let mut budget = plant::PlantTask::BUDGET;
+
+    for (entity, mut task, growth, instance, mut lod, mut mesh, mut metrics) in query.iter_mut() {
+        if budget == 0 {
+            break;
+        }
+
+        let new_meshes = match future::block_on(future::poll_once(&mut task.0)) {
+            Some(new_meshes) => new_meshes,
+            None => continue,
+        };
+
+        budget -= 1;
+
+        if growth.fully_grown() {
+            let (lods, new_metrics) = cache.share(*instance, new_meshes, &mut meshes);
+
+            *mesh = lods[lod.current].clone();
+            lod.meshes = lods;
+            *metrics = new_metrics;
+
+            commands
+                .entity(entity)
+                .remove::<plant::PlantGrowthTask>()
+                .remove::<plant::PlantGrowth>();
+
+            continue;
+        }
+
+        for (handle, mesh) in lod.meshes.iter().zip(new_meshes.lods) {
+            if let Some(old) = meshes.get_mut(handle) {
+                *old = mesh;
+            }
+        }
+
+        *metrics = new_meshes.metrics;
+
+        commands.entity(entity).remove::<plant::PlantGrowthTask>();
+    }
+}
+
+/// The resources [`plant_mesh_system`] grows plants with.
+#[derive(SystemParam)]
+pub struct PlantMeshResources<'a> {
+    meshes: Res<'a, Assets<Mesh>>,
+    cache: Res<'a, plant::PlantMeshCache>,
+    world_seed: Res<'a, plant::WorldSeed>,
+    season: Res<'a, organ::Season>,
+    occlusion: Res<'a, occlusion::PlantOcclusion>,
+    task_pool: Res<'a, bevy::tasks::AsyncComputeTaskPool>,
+}
+
+/// A plant still to be grown, with the seed and age it may have been given.
+type UngrownPlant<G> = (
+    Entity,
+    &'static Handle<G>,
+    &'static Transform,
+    Option<&'static plant::PlantSeed>,
+    Option<&'static plant::PlantAge>,
+);
+
+/// Plants that neither have meshes nor are growing them.
+type Ungrown = (Without<Handle<Mesh>>, Without<plant::PlantTask>);
+
+/// Starts growing every plant without a mesh on the task pool, see
+/// [`plant_task_system`] for where they receive their meshes. Fully grown plants
+/// whose meshes are already cached get them right away.
+pub fn plant_mesh_system<G: plant::PlantGenerator>(
+    mut commands: Commands,
+    plants: PlantMeshResources,
+    gnomes: Res<Assets<G>>,
+    sun_query: Query<&GlobalTransform, With<sun::Sun>>,
+    query: Query<UngrownPlant<G>, Ungrown>,
+) {
+    let PlantMeshResources {
+        meshes,
+        cache,
+        world_seed,
+        season,
+        occlusion,
+        task_pool,
+    } = plants;
+
+    // plants grow toward where the sun is when they're planted
+    let sun = sun_query
+        .iter()
+        .next()
+        .map_or(Vec3::Y, |sun| sun.rotation * Vec3::Z);
+
+    for (entity, genome_handle, transform, plant_seed, age) in query.iter() {
         if let Some(genome) = gnomes.get(genome_handle) {
-            let mesh_handle = meshes.add(genome.generate_mesh());
+            let seed = genome.instance_seed(
+                plant_seed.copied(),
+                plant::PlantSeed::from_position(*world_seed, transform.translation),
+            );
+
+            let age = age.copied().unwrap_or_default();
+            let light = transform.rotation.inverse() * sun;
+            let instance = plant::PlantInstance::new(
+                genome_handle.id,
+                seed,
+                genome.follows_light().then_some(light),
+            );
+
+            // only a seed given to the plant is kept as its `PlantSeed`, the derived one
+            // lives on in its `PlantInstance`
+            commands.entity(entity).insert(age).insert(instance);
+
+            if age.0 >= genome.growth().max_age {
+                if let Some((lods, metrics)) = cache.get(instance, &meshes) {
+                    commands
+                        .entity(entity)
+                        .insert(lods[0].clone())
+                        .insert(plant::PlantLod::new(lods))
+                        .insert(metrics);
+
+                    continue;
+                }
+            }
+
+            commands.entity(entity).insert(plant::PlantTask::spawn(
+                &task_pool,
+                genome,
+                seed,
+                age.0,
+                *season,
+                instance.light().unwrap_or(light),
+                *occlusion,
+            ));
+        }
+    }
+}
+
+/// Gives finished plants their meshes, at most [`plant::PlantTask::BUDGET`] each
+/// frame. Until then a plant has no mesh and isn't drawn.
+pub fn plant_task_system(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut cache: ResMut<plant::PlantMeshCache>,
+    mut query: Query<(Entity, &mut plant::PlantTask, &plant::PlantInstance)>,
+) {
+    let mut budget = plant::PlantTask::BUDGET;
+
+    for (entity, mut task, instance) in query.iter_mut() {
+        if budget == 0 {
+            break;
+        }
+
+        let (growth, new_meshes) = match future::block_on(future::poll_once(&mut task.0)) {
+            Some(result) => result,
+            None => continue,
+        };
+
+        budget -= 1;
+
+        if growth.fully_grown() {
+            let (lods, metrics) = cache.share(*instance, new_meshes, &mut meshes);
+
+            commands
+                .entity(entity)
+                .insert(lods[0].clone())
+                .insert(plant::PlantLod::new(lods))
+                .insert(metrics)
+                .remove::<plant::PlantTask>();
+
+            continue;
+        }
+
+        let lods = new_meshes
+            .lods
+            .into_iter()
+            .map(|mesh| meshes.add(mesh))
+            .collect::<Vec<_>>();
+
+        commands
+            .entity(entity)
+            .insert(lods[0].clone())
+            .insert(plant::PlantLod::new(lods))
+            .insert(growth)
+            .insert(new_meshes.metrics)
+            .remove::<plant::PlantTask>();
+    }
+}
+
+/// Plants that have meshes or are growing them, which [`strip_plant`] undoes.
+type GrownOrGrowing = Or<(With<Handle<Mesh>>, With<plant::PlantTask>)>;
+
+/// Strips the meshes from every plant whose asset changed on disk, so
+/// `plant_mesh_system` grows them again with the same seed and age. Cached meshes of
+/// the asset are evicted so they aren't handed out again.
+///
+/// Dropping `PlantLod` drops the last strong handles to the old meshes, which frees them,
+/// and dropping a pending task cancels it.
+pub fn plant_reload_system<G: plant::PlantGenerator>(
+    mut commands: Commands,
+    mut events: EventReader<AssetEvent<G>>,
+    mut cache: ResMut<plant::PlantMeshCache>,
+    query: Query<(Entity, &Handle<G>), GrownOrGrowing>,
+) {
+    let modified = events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.id),
+            _ => None,
+        })
+        .collect::<bevy::utils::HashSet<_>>();
+
+    if modified.is_empty() {
+        return;
+    }
+
+    for &id in &modified {
+        cache.evict(id);
+    }
+
+    for (entity, handle) in query.iter() {
+        if modified.contains(&handle.id) {
+            strip_plant(&mut commands, entity);
+        }
+    }
+}
+
+/// Moves the season on by a month when F11 is pressed, so organs can be seen coming
+/// and going.
+pub fn season_system(key: Res<Input<KeyCode>>, mut season: ResMut<organ::Season>) {
+    if key.just_pressed(KeyCode::F11) {
+        season.0 = (season.0 + 1.0 / 12.0).fract();
+
+        info!("season is now {:.2}", season.0);
+    }
+}
+
+/// Regrows every plant with organs that came into or went out of season since the
+/// last frame, and forgets the meshes cached for them.
+pub fn plant_season_system(
+    mut commands: Commands,
+    season: Res<organ::Season>,
+    mut last: Local<Option<organ::Season>>,
+    genomes: Res<Assets<plant::Genome>>,
+    mut cache: ResMut<plant::PlantMeshCache>,
+    query: Query<(Entity, &Handle<plant::Genome>), GrownOrGrowing>,
+) {
+    let previous = match last.replace(*season) {
+        Some(previous) if previous != *season => previous,
+        _ => return,
+    };
+
+    let changed = genomes
+        .iter()
+        .filter(|(_, genome)| {
+            genome
+                .organs
+                .iter()
+                .any(|organ| organ.in_season(previous.0) != organ.in_season(season.0))
+        })
+        .map(|(id, _)| id)
+        .collect::<bevy::utils::HashSet<_>>();
+
+    for &id in &changed {
+        cache.evict(id);
+    }
+
+    for (entity, handle) in query.iter() {
+        if changed.contains(&handle.id) {
+            strip_plant(&mut commands, entity);
+        }
+    }
+}
+
+/// Removes everything `plant_mesh_system` gives a plant, so it's grown again.
+fn strip_plant(commands: &mut Commands, entity: Entity) {
+    commands
+        .entity(entity)
+        .remove::<Handle<Mesh>>()
+        .remove::<plant::PlantLod>()
+        .remove::<plant::PlantGrowth>()
+        .remove::<plant::PlantTask>()
+        .remove::<plant::PlantGrowthTask>();
+}
+
+pub fn plant_lod_system(
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+    mut query: Query<(&GlobalTransform, &mut plant::PlantLod, &mut Handle<Mesh>)>,
+) {
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera.translation,
+        None => return,
+    };
+
+    for (transform, mut lod, mut mesh) in query.iter_mut() {
+        let level = plant::PlantLod::level(transform.translation.distance(camera))
+            .min(lod.meshes.len().saturating_sub(1));
+
+        if level != lod.current {
+            lod.current = level;
+            *mesh = lod.meshes[level].clone();
+        }
+    }
+}
+
+/// Exports the full detail mesh of the plant closest to the camera when F12 is pressed.
+pub fn plant_export_system(
+    key: Res<Input<KeyCode>>,
+    meshes: Res<Assets<Mesh>>,
+    camera_query: Query<&GlobalTransform, With<PlayerCamera>>,
+    query: Query<(&GlobalTransform, &plant::PlantLod)>,
+) {
+    if !key.just_pressed(KeyCode::F12) {
+        return;
+    }
+
+    let camera = match camera_query.iter().next() {
+        Some(camera) => camera.translation,
+        None => return,
+    };
+
+    let closest = query.iter().min_by(|(a, _), (b, _)| {
+        let a = a.translation.distance_squared(camera);
+        let b = b.translation.distance_squared(camera);
+
+        a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+    });
+
+    let mesh = match closest.and_then(|(_, lod)| meshes.get(&lod.meshes[0])) {
+        Some(mesh) => mesh,
+        None => return,
+    };
+
+    let directory = std::path::Path::new("exports");
+
+    if let Err(e) = std::fs::create_dir_all(directory) {
+        error!("failed to create '{}': {}", directory.display(), e);
+        return;
+    }
+
+    let name = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+
+    for extension in &["glb", "obj"] {
+        let path = directory.join(format!("plant_{}.{}", name, extension));
 
-            commands.entity(entity).insert(mesh_handle);
+        match export::export_mesh(mesh, &path) {
+            Ok(()) => info!("exported plant to '{}'", path.display()),
+            Err(e) => error!("failed to export '{}': {}", path.display(), e),
         }
     }
 }
@@ -0,0 +1,179 @@
+use crate::plant::PlantLod;
+use crate::skeleton::*;
+use bevy::{
+    diagnostic::{Diagnostic, DiagnosticId, Diagnostics},
+    prelude::*,
+};
+use serde::Serialize;
+
+/// Measurements of one mesh of a generated plant, used to budget scenes and tune
+/// species. Plants in the world carry the metrics of their full detail mesh, LOD 0,
+/// along with the size of every level of detail in `lod_triangles` and `lod_vertices`.
+#[derive(Serialize, Clone, Debug, Default)]
+pub struct PlantMetrics {
+    pub triangles: usize,
+    pub vertices: usize,
+    /// Triangles of each level of detail starting with LOD 0, empty when the metrics
+    /// were measured on a single mesh.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lod_triangles: Vec<usize>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lod_vertices: Vec<usize>,
+    pub leaves: usize,
+    /// Blossoms, fruit and other organs besides leaves.
+    pub organs: usize,
+    pub branches: usize,
+    /// Number of branches at each split level, starting with the trunk.
+    pub branches_per_level: Vec<usize>,
+    /// Root branches, which aren't counted among the branches.
+    pub roots: usize,
+    pub height: f32,
+    /// Widest horizontal extent of the mesh along x or z.
+    pub crown_width: f32,
+    pub trunk_radius: f32,
+    pub wood_volume: f32,
+    pub leaf_area: f32,
+}
+
+impl PlantMetrics {
+    pub(crate) fn measure(
+        skeleton: &PlantSkeleton,
+        vertices: &[Vec3],
+        indices: &[u32],
+        material: &[u32],
+        leaves: usize,
+    ) -> Self {
+        let mut branches_per_level = Vec::new();
+        let mut roots = 0;
+        let mut wood_volume = 0.0;
+
+        for branch in &skeleton.branches {
+            if branch.root {
+                roots += 1;
+            } else {
+                if branches_per_level.len() <= branch.split {
+                    branches_per_level.resize(branch.split + 1, 0);
+                }
+
+                branches_per_level[branch.split] += 1;
+            }
+
+            // every segment is a frustum between two frames
+            for segment in branch.frames.windows(2) {
+                let (a, b) = (segment[0].radius, segment[1].radius);
+                let length = segment[0].position.distance(segment[1].position);
+
+                wood_volume += std::f32::consts::PI * length / 3.0 * (a * a + a * b + b * b);
+            }
+        }
+
+        let leaf_area = indices
+            .chunks_exact(3)
+            .filter(|triangle| material[triangle[0] as usize] == 1)
+            .map(|triangle| {
+                let v0 = vertices[triangle[0] as usize];
+                let v1 = vertices[triangle[1] as usize];
+                let v2 = vertices[triangle[2] as usize];
+
+                (v1 - v0).cross(v2 - v0).length() / 2.0
+            })
+            .sum();
+
+        let (min, max) = vertices.iter().fold(
+            (Vec3::splat(f32::MAX), Vec3::splat(f32::MIN)),
+            |(min, max), v| (min.min(*v), max.max(*v)),
+        );
+        let size = if vertices.is_empty() {
+            Vec3::ZERO
+        } else {
+            max - min
+        };
+
+        Self {
+            triangles: indices.len() / 3,
+            vertices: vertices.len(),
+            lod_triangles: Vec::new(),
+            lod_vertices: Vec::new(),
+            leaves,
+            organs: skeleton
+                .branches
+                .iter()
+                .map(|branch| branch.organs.len())
+                .sum(),
+            branches: skeleton.branches.len() - roots,
+            branches_per_level,
+            roots,
+            height: max.y.max(0.0),
+            crown_width: size.x.max(size.z),
+            trunk_radius: skeleton
+                .branches
+                .iter()
+                .filter(|branch| branch.parent.is_none())
+                .map(|branch| branch.start_radius)
+                .fold(0.0, f32::max),
+            wood_volume,
+            leaf_area,
+        }
+    }
+}
+
+pub const PLANTS: DiagnosticId = DiagnosticId::from_u128(0x6d2f1c3e8a7b4f05b9e1c4a2d3f60718);
+pub const PLANT_TRIANGLES: DiagnosticId =
+    DiagnosticId::from_u128(0x1a9c5e7f2b3d4c68a0f1e2d3c4b5a697);
+pub const PLANT_VERTICES: DiagnosticId =
+    DiagnosticId::from_u128(0x3e8b1d6c9f2a4e71b5c0d9e8f7a6b524);
+pub const PLANT_LEAVES: DiagnosticId = DiagnosticId::from_u128(0x57c2a9e1d4b84f3a9e6d1c0b2a3f4e85);
+pub const PLANT_WOOD_VOLUME: DiagnosticId =
+    DiagnosticId::from_u128(0x8f4d2b7a1c6e4d09a3b8e5f2c1d0a946);
+pub const PLANT_LEAF_AREA: DiagnosticId =
+    DiagnosticId::from_u128(0x92e6c3a8b1f54d7e8c2a0b9d4e3f1a57);
+
+const DIAGNOSTICS: [(DiagnosticId, &str); 6] = [
+    (PLANTS, "plants"),
+    (PLANT_TRIANGLES, "plant_triangles"),
+    (PLANT_VERTICES, "plant_vertices"),
+    (PLANT_LEAVES, "plant_leaves"),
+    (PLANT_WOOD_VOLUME, "plant_wood_volume"),
+    (PLANT_LEAF_AREA, "plant_leaf_area"),
+];
+
+pub fn setup_plant_diagnostics(mut diagnostics: ResMut<Diagnostics>) {
+    for &(id, name) in DIAGNOSTICS.iter() {
+        diagnostics.add(Diagnostic::new(id, name, 20));
+    }
+}
+
+/// Sums the metrics of every plant in the world. Triangles and vertices are counted
+/// for the level of detail each plant shows, the rest are measured on LOD 0.
+pub fn plant_diagnostics_system(
+    mut diagnostics: ResMut<Diagnostics>,
+    query: Query<(&PlantMetrics, Option<&PlantLod>)>,
+) {
+    let mut totals = [0.0; 6];
+
+    for (metrics, lod) in query.iter() {
+        let current = lod.map_or(0, |lod| lod.current);
+        let triangles = metrics.lod_triangles.get(current);
+        let vertices = metrics.lod_vertices.get(current);
+
+        totals[0] += 1.0;
+        totals[1] += *triangles.unwrap_or(&metrics.triangles) as f64;
+        totals[2] += *vertices.unwrap_or(&metrics.vertices) as f64;
+        totals[3] += metrics.leaves as f64;
+        totals[4] += metrics.wood_volume as f64;
+        totals[5] += metrics.leaf_area as f64;
+    }
+
+    for ((id, _), total) in DIAGNOSTICS.iter().zip(totals.iter()) {
+        diagnostics.add_measurement(*id, *total);
+    }
+}
+
+pub struct PlantDiagnosticsPlugin;
+
+impl Plugin for PlantDiagnosticsPlugin {
+    fn build(&self, app_builder: &mut AppBuilder) {
+        app_builder.add_startup_system(setup_plant_diagnostics.system());
+        app_builder.add_system(plant_diagnostics_system.system());
+    }
+}
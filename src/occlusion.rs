@@ -0,0 +1,382 @@
+use crate::phyllotaxis::GOLDEN_ANGLE;
+use bevy::prelude::*;
+use bevy::utils::HashMap;
+
+/// Ambient occlusion baked into the vertex colors of a plant mesh, so the inside of
+/// a crown is darker than its outer shell.
+///
+/// Every vertex casts rays over the hemisphere around its normal, over the whole
+/// sphere for leaf and blossom cards which are seen from both sides, and is darkened
+/// by the share that hit the plant's own triangles within `distance`. Nearer hits
+/// count for more.
+#[derive(Clone, Copy, Debug)]
+pub struct OcclusionBake {
+    pub rays: usize,
+    /// How far away triangles still occlude.
+    pub distance: f32,
+    /// How dark a fully occluded vertex gets, 0 leaves colors as they are.
+    pub strength: f32,
+}
+
+impl Default for OcclusionBake {
+    fn default() -> Self {
+        Self {
+            rays: 8,
+            distance: 1.0,
+            strength: 0.6,
+        }
+    }
+}
+
+/// The ambient occlusion plants are baked with, `None` leaves their colors as they
+/// are.
+///
+/// Only the full detail mesh of a fully grown plant is baked, coarser levels of
+/// detail are seen from too far away to tell and growing plants are re-meshed too
+/// often to be worth it.
+#[derive(Clone, Copy, Debug)]
+pub struct PlantOcclusion(pub Option<OcclusionBake>);
+
+impl Default for PlantOcclusion {
+    fn default() -> Self {
+        Self(Some(OcclusionBake::default()))
+    }
+}
+
+impl OcclusionBake {
+    /// How much of the sky every vertex sees, 1 for a vertex nothing occludes.
+    ///
+    /// `two_sided` vertices cast rays to both sides of their normal.
+    pub fn bake(
+        &self,
+        vertices: &[Vec3],
+        normals: &[Vec3],
+        indices: &[u32],
+        two_sided: impl Fn(usize) -> bool,
+    ) -> Vec<f32> {
+        // a few cells per `distance` keep the triangles tested per cell few in dense
+        // crowns, without one large triangle spreading over many of them
+        let grid = TriangleGrid::new(vertices, indices, self.distance / 5.0);
+        let directions = self.directions();
+
+        vertices
+            .iter()
+            .zip(normals)
+            .enumerate()
+            .map(|(i, (&vertex, &normal))| {
+                if normal == Vec3::ZERO || self.rays == 0 {
+                    return 1.0;
+                }
+
+                let front = Quat::from_rotation_arc(Vec3::Z, normal);
+                let back = Quat::from_rotation_arc(Vec3::Z, -normal);
+                let two_sided = two_sided(i);
+
+                let occlusion: f32 = directions
+                    .iter()
+                    .enumerate()
+                    .filter_map(|(ray, &direction)| {
+                        // two sided vertices alternate sides between rays
+                        let (side, normal) = if two_sided && ray % 2 == 1 {
+                            (back, -normal)
+                        } else {
+                            (front, normal)
+                        };
+
+                        grid.hit(
+                            i as u32,
+                            vertex + normal * 0.01,
+                            side * direction,
+                            self.distance,
+                        )
+                    })
+                    .map(|t| 1.0 - t / self.distance)
+                    .sum();
+
+                1.0 - self.strength * occlusion / self.rays as f32
+            })
+            .collect()
+    }
+
+    /// Ray directions spread evenly over the hemisphere around +z, more of them
+    /// toward the pole where occlusion matters most.
+    fn directions(&self) -> Vec<Vec3> {
+        (0..self.rays)
+            .map(|ray| {
+                let z = (1.0 - (ray as f32 + 0.5) / self.rays as f32).sqrt();
+                let r = (1.0 - z * z).sqrt();
+                let angle = ray as f32 * GOLDEN_ANGLE;
+
+                Vec3::new(angle.cos() * r, angle.sin() * r, z)
+            })
+            .collect()
+    }
+}
+
+/// The triangles of a mesh sorted into the cells of a grid, so a ray only has to be
+/// tested against the triangles near it. Only cells holding triangles are stored, so
+/// it grows with the surface of the plant rather than the volume around it.
+struct TriangleGrid<'a> {
+    vertices: &'a [Vec3],
+    indices: &'a [u32],
+    cell: f32,
+    cells: HashMap<(i32, i32, i32), Vec<u32>>,
+    /// The lowest and highest cell holding a triangle on each axis.
+    bounds: [(i32, i32); 3],
+}
+
+impl<'a> TriangleGrid<'a> {
+    fn new(vertices: &'a [Vec3], indices: &'a [u32], cell: f32) -> Self {
+        let mut grid = Self {
+            vertices,
+            indices,
+            cell,
+            cells: HashMap::default(),
+            bounds: [(i32::MAX, i32::MIN); 3],
+        };
+
+        for (triangle, corners) in indices.chunks_exact(3).enumerate() {
+            let corners = [
+                vertices[corners[0] as usize],
+                vertices[corners[1] as usize],
+                vertices[corners[2] as usize],
+            ];
+
+            let low = grid.coordinates(corners[0].min(corners[1]).min(corners[2]));
+            let high = grid.coordinates(corners[0].max(corners[1]).max(corners[2]));
+
+            for (axis, (min, max)) in grid.bounds.iter_mut().enumerate() {
+                *min = (*min).min([low.0, low.1, low.2][axis]);
+                *max = (*max).max([high.0, high.1, high.2][axis]);
+            }
+
+            for x in low.0..=high.0 {
+                for y in low.1..=high.1 {
+                    for z in low.2..=high.2 {
+                        grid.cells
+                            .entry((x, y, z))
+                            .or_default()
+                            .push(triangle as u32);
+                    }
+                }
+            }
+        }
+
+        grid
+    }
+
+    fn coordinates(&self, point: Vec3) -> (i32, i32, i32) {
+        let p = (point / self.cell).floor();
+
+        (p.x as i32, p.y as i32, p.z as i32)
+    }
+
+    /// How far along the ray from `origin` it first hits a triangle within
+    /// `distance`, other than those `vertex` is a corner of. The cells along the ray
+    /// are walked in order, so it's the nearest hit or close to it.
+    fn hit(&self, vertex: u32, origin: Vec3, direction: Vec3, distance: f32) -> Option<f32> {
+        let start = origin / self.cell;
+        let mut cell = self.coordinates(origin);
+        let mut current = [cell.0, cell.1, cell.2];
+        let mut step = [0; 3];
+        // how far along the ray it crosses into the next cell on each axis, and how
+        // far it goes between crossings
+        let mut next = [f32::INFINITY; 3];
+        let mut delta = [f32::INFINITY; 3];
+
+        for axis in 0..3 {
+            let d = direction[axis];
+
+            if d > 0.0 {
+                step[axis] = 1;
+                next[axis] = (current[axis] as f32 + 1.0 - start[axis]) * self.cell / d;
+                delta[axis] = self.cell / d;
+            } else if d < 0.0 {
+                step[axis] = -1;
+                next[axis] = (current[axis] as f32 - start[axis]) * self.cell / d;
+                delta[axis] = -self.cell / d;
+            }
+        }
+
+        loop {
+            let leaving = next[0].min(next[1]).min(next[2]);
+
+            for &triangle in self.cells.get(&cell).into_iter().flatten() {
+                let triangle = triangle as usize;
+                let corners = &self.indices[triangle * 3..triangle * 3 + 3];
+
+                if corners.contains(&vertex) {
+                    continue;
+                }
+
+                let [a, b, c] = [
+                    self.vertices[corners[0] as usize],
+                    self.vertices[corners[1] as usize],
+                    self.vertices[corners[2] as usize],
+                ];
+
+                // a hit past this cell is found again in the cell it lies in, once
+                // the cells before it are searched for nearer ones
+                match intersect(origin, direction, a, b, c) {
+                    Some(t) if t <= distance && t <= leaving => return Some(t),
+                    _ => {}
+                }
+            }
+
+            if leaving > distance {
+                return None;
+            }
+
+            let axis = if leaving == next[0] {
+                0
+            } else if leaving == next[1] {
+                1
+            } else {
+                2
+            };
+
+            current[axis] += step[axis];
+            next[axis] += delta[axis];
+
+            // the ray has left the mesh and won't come back
+            let (min, max) = self.bounds[axis];
+
+            if current[axis] < min || current[axis] > max {
+                return None;
+            }
+            cell = (current[0], current[1], current[2]);
+        }
+    }
+}
+
+/// Distance along the ray from `origin` at which it hits the triangle `a`, `b`,
+/// `c` from either side.
+fn intersect(origin: Vec3, direction: Vec3, a: Vec3, b: Vec3, c: Vec3) -> Option<f32> {
+    let ab = b - a;
+    let ac = c - a;
+    let p = direction.cross(ac);
+    let determinant = ab.dot(p);
+
+    if determinant.abs() < 1e-8 {
+        return None;
+    }
+
+    let inverse = 1.0 / determinant;
+    let to_origin = origin - a;
+    let u = to_origin.dot(p) * inverse;
+
+    if !(0.0..=1.0).contains(&u) {
+        return None;
+    }
+
+    let q = to_origin.cross(ab);
+    let v = direction.dot(q) * inverse;
+
+    if v < 0.0 || u + v > 1.0 {
+        return None;
+    }
+
+    let t = ac.dot(q) * inverse;
+
+    if t > 0.0 {
+        Some(t)
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Upward facing squares, centered over the origin, of the given heights and sizes.
+    fn squares(squares: &[(f32, f32)]) -> (Vec<Vec3>, Vec<Vec3>, Vec<u32>) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+
+        for &(y, size) in squares {
+            let first = vertices.len() as u32;
+            let half = size / 2.0;
+
+            vertices.extend_from_slice(&[
+                Vec3::new(-half, y, -half),
+                Vec3::new(half, y, -half),
+                Vec3::new(half, y, half),
+                Vec3::new(-half, y, half),
+            ]);
+            indices.extend([0, 2, 1, 0, 3, 2].iter().map(|i| first + i));
+        }
+
+        let normals = vec![Vec3::Y; vertices.len()];
+
+        (vertices, normals, indices)
+    }
+
+    fn bake(squares: &[(f32, f32)], two_sided: bool) -> Vec<f32> {
+        let (vertices, normals, indices) = self::squares(squares);
+
+        OcclusionBake::default().bake(&vertices, &normals, &indices, |_| two_sided)
+    }
+
+    #[test]
+    fn open_surfaces_see_the_whole_sky() {
+        assert!(bake(&[(0.0, 1.0)], true).iter().all(|&v| v == 1.0));
+
+        let (vertices, normals, indices) = squares(&[(0.0, 0.2), (0.3, 4.0)]);
+        let unlit = OcclusionBake {
+            rays: 0,
+            ..Default::default()
+        };
+
+        assert!(unlit
+            .bake(&vertices, &normals, &indices, |_| false)
+            .iter()
+            .all(|&v| v == 1.0));
+    }
+
+    #[test]
+    fn nearer_cover_occludes_more() {
+        let strength = OcclusionBake::default().strength;
+        let near = bake(&[(0.0, 0.2), (0.25, 4.0)], false);
+        let far = bake(&[(0.0, 0.2), (0.75, 4.0)], false);
+
+        assert!(near[..4].iter().all(|&v| v >= 1.0 - strength && v < 1.0));
+        assert!(near[..4]
+            .iter()
+            .zip(&far[..4])
+            .all(|(near, far)| near < far));
+        // the cover faces away from the ground below it
+        assert!(near[4..].iter().all(|&v| v == 1.0));
+        // beyond `distance` nothing occludes
+        assert!(bake(&[(0.0, 0.2), (1.5, 4.0)], false)
+            .iter()
+            .all(|&v| v == 1.0));
+    }
+
+    #[test]
+    fn two_sided_vertices_see_both_ways() {
+        let one_sided = bake(&[(0.0, 4.0), (0.25, 0.2)], false);
+        let two_sided = bake(&[(0.0, 4.0), (0.25, 0.2)], true);
+
+        assert!(one_sided[4..].iter().all(|&v| v == 1.0));
+        assert!(two_sided[4..].iter().all(|&v| v < 1.0));
+    }
+
+    #[test]
+    fn rays_hit_triangles_from_either_side() {
+        let [a, b, c] = [Vec3::ZERO, Vec3::X, Vec3::Z];
+        let origin = Vec3::new(0.25, 1.0, 0.25);
+
+        assert_eq!(intersect(origin, -Vec3::Y, a, b, c), Some(1.0));
+        assert_eq!(intersect(-origin, Vec3::Y, -a, -b, -c), Some(1.0));
+        assert_eq!(intersect(origin, Vec3::Y, a, b, c), None);
+        assert_eq!(
+            intersect(Vec3::new(0.75, 1.0, 0.75), -Vec3::Y, a, b, c),
+            None
+        );
+        assert!(OcclusionBake::default()
+            .directions()
+            .iter()
+            .all(|d| (d.length() - 1.0).abs() < 0.0001 && d.z > 0.0));
+    }
+}
@@ -0,0 +1,353 @@
+use crate::inheritance::present;
+use crate::plant::*;
+use crate::skeleton::*;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// What an organ is, which decides the material and texture it's drawn with.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum OrganKind {
+    Blossom,
+    Fruit,
+    Cone,
+    SeedPod,
+}
+
+impl OrganKind {
+    pub const ALL: [OrganKind; 4] = [Self::Blossom, Self::Fruit, Self::Cone, Self::SeedPod];
+
+    /// The `Plant_Material` id, following bark at 0 and leaves at 1.
+    pub fn material(self) -> u32 {
+        match self {
+            Self::Blossom => 2,
+            Self::Fruit => 3,
+            Self::Cone => 4,
+            Self::SeedPod => 5,
+        }
+    }
+
+    pub fn from_material(material: u32) -> Option<Self> {
+        Self::ALL
+            .iter()
+            .copied()
+            .find(|kind| kind.material() == material)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Self::Blossom => "blossom",
+            Self::Fruit => "fruit",
+            Self::Cone => "cone",
+            Self::SeedPod => "seed_pod",
+        }
+    }
+}
+
+/// Where on a branch organs grow.
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub enum OrganPlacement {
+    /// At the end of branches that don't split any further, each with `chance`.
+    Tips { chance: f32 },
+    /// Spread over the branch surface like leaves, `density` being the expected
+    /// number per branch.
+    Along { density: f32 },
+}
+
+/// The mesh template an organ is built from, one unit long before scaling by size.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub enum OrganShape {
+    /// A flat card facing away from where it's attached.
+    Card,
+    /// A closed body `segments` around, `length` times as long as it's wide.
+    Sphere { segments: usize, length: f32 },
+}
+
+/// A type of organ a genome grows, such as blossoms or fruit.
+///
+/// ```ron
+/// organs: [
+///     (
+///         kind: Fruit,
+///         placement: Along(density: 0.6),
+///         shape: Sphere(segments: 8, length: 1.0),
+///         size: 0.12,
+///         color: (0.8, 0.1, 0.05),
+///         hanging: true,
+///         season: (start: 0.6, end: 0.85),
+///     ),
+/// ],
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct OrganParams {
+    pub kind: OrganKind,
+    pub placement: OrganPlacement,
+    pub shape: OrganShape,
+    pub size: f32,
+    /// Tints the texture of the organ.
+    #[serde(default = "white")]
+    pub color: (f32, f32, f32),
+    /// The first split level organs grow on.
+    #[serde(default)]
+    pub start: usize,
+    /// Hang straight down from where they're attached instead of pointing away
+    /// from the branch.
+    #[serde(default)]
+    pub hanging: bool,
+    /// The part of the year the organs are out, see [`Season`]. Wraps around the
+    /// end of the year when `start` is greater than `end`, all year if left out.
+    #[serde(default, deserialize_with = "present")]
+    pub season: Option<Range<f32>>,
+}
+
+fn white() -> (f32, f32, f32) {
+    (1.0, 1.0, 1.0)
+}
+
+fn check_fraction(field: &'static str, value: f32) -> Result<(), GenomeError> {
+    if !value.is_finite() {
+        Err(GenomeError::NotFinite { field })
+    } else if !(0.0..=1.0).contains(&value) {
+        Err(GenomeError::OutOfRange {
+            field,
+            value: value as f64,
+            min: 0.0,
+            max: 1.0,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+/// Whether `time` of the year lies within `season`.
+fn in_season(season: Option<&Range<f32>>, time: f32) -> bool {
+    match season {
+        Some(season) if season.start <= season.end => season.contains(&time),
+        Some(season) => time >= season.start || time < season.end,
+        None => true,
+    }
+}
+
+impl OrganParams {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        match self.placement {
+            OrganPlacement::Tips { chance } => check_fraction("placement.chance", chance)?,
+            OrganPlacement::Along { density } => check_non_negative("placement.density", density)?,
+        }
+
+        if let OrganShape::Sphere { segments, length } = self.shape {
+            check_count("shape.segments", segments, 3)?;
+            check_positive("shape.length", length)?;
+        }
+
+        check_positive("size", self.size)?;
+
+        let (r, g, b) = self.color;
+        check_non_negative("color", r)?;
+        check_non_negative("color", g)?;
+        check_non_negative("color", b)?;
+
+        if let Some(season) = &self.season {
+            check_fraction("season.start", season.start)?;
+            check_fraction("season.end", season.end)?;
+        }
+
+        Ok(())
+    }
+
+    pub fn in_season(&self, time: f32) -> bool {
+        in_season(self.season.as_ref(), time)
+    }
+
+    /// Places organs of this type on the branch made of `frames` at split level
+    /// `split`, `tip` being whether nothing splits off the end of the branch.
+    pub fn place(
+        &self,
+        rng: &mut rand::rngs::SmallRng,
+        split: usize,
+        frames: &[SegmentFrame],
+        radial_segments: usize,
+        tip: bool,
+        organs: &mut Vec<Organ>,
+    ) {
+        if split < self.start || frames.len() < 2 {
+            return;
+        }
+
+        match self.placement {
+            OrganPlacement::Tips { chance } => {
+                let last = &frames[frames.len() - 1];
+
+                if tip && rng.gen_range(0.0..1.0) < chance {
+                    organs.push(self.organ(rng, last.position, last.rotation * Vec3::Y, last));
+                }
+            }
+            OrganPlacement::Along { density } => {
+                let chance = density / (frames.len() - 1) as f32 / radial_segments as f32;
+
+                for frame in &frames[1..] {
+                    for vert in frame.ring(radial_segments).verts {
+                        if rng.gen_range(0.0..1.0) <= chance {
+                            let outward = vert - frame.position;
+
+                            organs.push(self.organ(rng, vert, outward, frame));
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    fn organ(
+        &self,
+        rng: &mut rand::rngs::SmallRng,
+        pos: Vec3,
+        forward: Vec3,
+        frame: &SegmentFrame,
+    ) -> Organ {
+        let forward = if self.hanging {
+            -Vec3::Y
+        } else {
+            forward.try_normalize().unwrap_or(Vec3::Y)
+        };
+
+        // spun randomly around the axis so neighbours don't look stamped out
+        let spin = Quat::from_axis_angle(forward, rng.gen_range(0.0..std::f32::consts::TAU));
+        let (r, g, b) = self.color;
+
+        Organ {
+            kind: self.kind,
+            shape: self.shape,
+            pos,
+            rot: spin * Quat::from_rotation_arc(Vec3::Z, forward),
+            sway: frame.sway,
+            size: self.size,
+            color: Color::rgb(r, g, b),
+            season: self.season.clone(),
+        }
+    }
+}
+
+impl Genome {
+    /// Every organ growing on a branch, see [`OrganParams::place`].
+    pub fn place_organs(
+        &self,
+        rng: &mut rand::rngs::SmallRng,
+        split: usize,
+        frames: &[SegmentFrame],
+        radial_segments: usize,
+        tip: bool,
+    ) -> Vec<Organ> {
+        let mut organs = Vec::new();
+
+        for params in &self.organs {
+            params.place(rng, split, frames, radial_segments, tip, &mut organs);
+        }
+
+        organs
+    }
+}
+
+/// A single organ attached to a branch, it extends along its local z axis.
+#[derive(Clone)]
+pub struct Organ {
+    pub kind: OrganKind,
+    pub shape: OrganShape,
+    pub pos: Vec3,
+    pub rot: Quat,
+    pub sway: f32,
+    pub size: f32,
+    pub color: Color,
+    pub season: Option<Range<f32>>,
+}
+
+impl Organ {
+    pub fn in_season(&self, time: f32) -> bool {
+        in_season(self.season.as_ref(), time)
+    }
+
+    pub fn generate_mesh(&self, ctx: &mut PlantContext<'_>) {
+        let first = ctx.vertices.len() as u32;
+
+        match self.shape {
+            OrganShape::Card => {
+                let verts = [
+                    Vec3::new(-0.5, -0.5, 0.25),
+                    Vec3::new(0.5, -0.5, 0.25),
+                    Vec3::new(-0.5, 0.5, 0.25),
+                    Vec3::new(0.5, 0.5, 0.25),
+                ];
+
+                for v in verts.iter() {
+                    ctx.vertices.push(self.transform(*v));
+                    ctx.uv.push(Vec2::new(v.x + 0.5, v.y + 0.5));
+                }
+
+                ctx.indices
+                    .extend([0, 1, 2, 1, 3, 2].iter().map(|i| first + i));
+            }
+            OrganShape::Sphere { segments, length } => {
+                let rings = (segments / 2).max(2);
+
+                for ring in 0..=rings {
+                    let angle = ring as f32 / rings as f32 * std::f32::consts::PI;
+                    let z = (1.0 - angle.cos()) / 2.0 * length;
+                    let radius = angle.sin() / 2.0;
+
+                    // the first column is repeated at the end so the texture wraps
+                    for segment in 0..=segments {
+                        let around = segment as f32 / segments as f32;
+                        let (sin, cos) = (around * std::f32::consts::TAU).sin_cos();
+
+                        ctx.vertices
+                            .push(self.transform(Vec3::new(cos * radius, sin * radius, z)));
+                        ctx.uv.push(Vec2::new(around, ring as f32 / rings as f32));
+                    }
+                }
+
+                let columns = segments as u32 + 1;
+
+                for ring in 0..rings as u32 {
+                    for segment in 0..segments as u32 {
+                        let a = first + ring * columns + segment;
+                        let b = a + 1;
+                        let c = a + columns;
+                        let d = c + 1;
+
+                        // the poles are single points, one triangle per quad reaches them
+                        if ring != 0 {
+                            ctx.indices.extend_from_slice(&[a, c, b]);
+                        }
+
+                        if ring + 1 != rings as u32 {
+                            ctx.indices.extend_from_slice(&[b, c, d]);
+                        }
+                    }
+                }
+            }
+        }
+
+        for _ in first as usize..ctx.vertices.len() {
+            ctx.color.push(self.color);
+            ctx.sway.push(self.sway);
+            ctx.material.push(self.kind.material());
+        }
+    }
+
+    fn transform(&self, v: Vec3) -> Vec3 {
+        self.rot * (v * self.size) + self.pos
+    }
+}
+
+/// The time of year, 0 to 1 starting at the beginning of spring, which decides
+/// which organs are out. Plants are regrown when it moves in or out of their seasons.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Season(pub f32);
+
+impl Default for Season {
+    /// Late spring.
+    fn default() -> Self {
+        Self(0.2)
+    }
+}
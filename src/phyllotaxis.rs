@@ -0,0 +1,255 @@
+use crate::level::*;
+use crate::plant::*;
+use crate::skeleton::*;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
+/// The golden angle in radians, which never lines two leaves up.
+pub const GOLDEN_ANGLE: f32 = 2.399_963;
+
+/// How leaves and child branches are arranged around the branch they grow on.
+///
+/// Leaves grow at nodes spaced evenly along their branch, as many nodes as it takes
+/// to reach `leaf_density`. Child branches take the angles of consecutive leaves,
+/// which is why `Alternate` and `Spiral` suit one or two branches per split.
+///
+/// ```ron
+/// phyllotaxis: Opposite,
+/// phyllotaxis: Whorled(count: 3),
+/// phyllotaxis: Spiral(),
+/// phyllotaxis: Spiral(angle: 1.7),
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug, Default, PartialEq)]
+pub enum Phyllotaxis {
+    /// Leaves are scattered over the branch surface and branches are spread evenly
+    /// with some jitter.
+    #[default]
+    Random,
+    /// One leaf per node, each on the opposite side from the last.
+    Alternate,
+    /// Pairs of leaves facing each other, every pair a quarter turn from the last.
+    Opposite,
+    /// `count` leaves evenly around every node, every whorl turned to sit in the
+    /// gaps of the last.
+    Whorled { count: usize },
+    /// One leaf per node, each `angle` radians around from the last.
+    Spiral {
+        #[serde(default = "golden_angle")]
+        angle: f32,
+    },
+}
+
+fn golden_angle() -> f32 {
+    GOLDEN_ANGLE
+}
+
+impl Phyllotaxis {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        match self {
+            Self::Whorled { count } => check_count("phyllotaxis.count", *count, 2),
+            Self::Spiral { angle } => check_non_negative("phyllotaxis.angle", *angle),
+            _ => Ok(()),
+        }
+    }
+
+    /// How many leaves grow at each node, none for `Random`.
+    pub fn per_node(&self) -> usize {
+        match self {
+            Self::Random => 0,
+            Self::Alternate | Self::Spiral { .. } => 1,
+            Self::Opposite => 2,
+            Self::Whorled { count } => *count,
+        }
+    }
+
+    /// Angle around the branch of leaf `leaf` at node `node`.
+    pub fn angle(&self, node: usize, leaf: usize) -> f32 {
+        let (node, leaf) = (node as f32, leaf as f32);
+
+        match self {
+            Self::Random => 0.0,
+            Self::Alternate => node * PI,
+            Self::Opposite => node * FRAC_PI_2 + leaf * PI,
+            Self::Whorled { count } => {
+                let count = *count as f32;
+
+                node * PI / count + leaf * TAU / count
+            }
+            Self::Spiral { angle } => node * angle,
+        }
+    }
+
+    /// Angle of the `index`th organ counting along the branch, node by node.
+    pub fn nth_angle(&self, index: usize) -> f32 {
+        let per_node = self.per_node().max(1);
+
+        self.angle(index / per_node, index % per_node) % TAU
+    }
+}
+
+/// Places leaves along a branch one segment at a time, keeping track of the nodes
+/// placed so far.
+pub struct LeafNodes<'a> {
+    phyllotaxis: &'a Phyllotaxis,
+    /// Number of segments `leaf_density` is spread over.
+    segments: usize,
+    radial_segments: usize,
+    /// Fraction of a node left over from the previous segments.
+    carry: f32,
+    node: usize,
+}
+
+impl<'a> LeafNodes<'a> {
+    /// Starts counting nodes at `node`, so a branch carries on from the node it grew
+    /// out of instead of repeating its siblings' angles.
+    pub fn new(
+        phyllotaxis: &'a Phyllotaxis,
+        segments: usize,
+        radial_segments: usize,
+        node: usize,
+    ) -> Self {
+        Self {
+            phyllotaxis,
+            segments,
+            radial_segments,
+            carry: 0.0,
+            node,
+        }
+    }
+
+    /// Angle of the `index`th child branch, counting on from the nodes placed on
+    /// this branch so far.
+    pub fn child_angle(&self, index: usize) -> f32 {
+        self.phyllotaxis
+            .nth_angle(self.node * self.phyllotaxis.per_node() + index)
+    }
+
+    /// Node the `index`th child branch starts counting its own nodes from, the one
+    /// after the node it grows at.
+    pub fn child_node(&self, index: usize) -> usize {
+        self.node + index / self.phyllotaxis.per_node().max(1) + 1
+    }
+
+    /// Adds the leaves of the segment from `prev` to `frame`.
+    pub fn place(
+        &mut self,
+        rng: &mut rand::rngs::SmallRng,
+        level: &Level,
+        prev: &SegmentFrame,
+        frame: &SegmentFrame,
+        leaves: &mut Vec<Leaf>,
+    ) {
+        let per_node = self.phyllotaxis.per_node();
+
+        if per_node == 0 {
+            let chance = level.leaf_density / self.segments as f32 / self.radial_segments as f32;
+
+            for vert in frame.ring(self.radial_segments).verts {
+                if rng.gen_range(0.0..1.0) <= chance {
+                    leaves.push(Leaf::random(rng, level, vert, frame));
+                }
+            }
+
+            return;
+        }
+
+        self.carry += level.leaf_density / self.segments as f32 / per_node as f32;
+        let nodes = self.carry.floor() as usize;
+        self.carry -= nodes as f32;
+
+        for i in 0..nodes {
+            let node = prev.lerp(frame, (i + 1) as f32 / nodes as f32);
+
+            for leaf in 0..per_node {
+                let angle = self.phyllotaxis.angle(self.node, leaf);
+                let offset = Vec3::new(angle.cos(), 0.0, angle.sin()) * node.radius;
+                let vert = node.position + node.rotation * offset;
+
+                leaves.push(Leaf::random(rng, level, vert, &node));
+            }
+
+            self.node += 1;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_angle(angle: f32, expected: f32) {
+        assert!(
+            (angle - expected).abs() < 0.0001,
+            "{} != {}",
+            angle,
+            expected
+        );
+    }
+
+    #[test]
+    fn angles_of_each_arrangement() {
+        assert_angle(Phyllotaxis::Alternate.angle(3, 0), 3.0 * PI);
+        assert_angle(Phyllotaxis::Opposite.angle(1, 1), FRAC_PI_2 + PI);
+        assert_angle(
+            Phyllotaxis::Whorled { count: 3 }.angle(1, 2),
+            PI / 3.0 + 2.0 * TAU / 3.0,
+        );
+        assert_angle(Phyllotaxis::Spiral { angle: 0.5 }.angle(4, 0), 2.0);
+        assert_angle(Phyllotaxis::Random.angle(4, 1), 0.0);
+    }
+
+    #[test]
+    fn nth_angle_counts_node_by_node() {
+        let opposite = Phyllotaxis::Opposite;
+
+        assert_angle(opposite.nth_angle(0), 0.0);
+        assert_angle(opposite.nth_angle(1), PI);
+        assert_angle(opposite.nth_angle(2), FRAC_PI_2);
+        assert_angle(opposite.nth_angle(3), FRAC_PI_2 + PI);
+        assert_angle(Phyllotaxis::Alternate.nth_angle(2), 0.0);
+        assert_angle(
+            Phyllotaxis::Spiral {
+                angle: GOLDEN_ANGLE,
+            }
+            .nth_angle(3),
+            (3.0 * GOLDEN_ANGLE) % TAU,
+        );
+    }
+
+    #[test]
+    fn children_count_on_from_their_parents_node() {
+        let whorled = Phyllotaxis::Whorled { count: 2 };
+        let nodes = LeafNodes::new(&whorled, 4, 8, 3);
+
+        assert_angle(nodes.child_angle(0), whorled.nth_angle(6));
+        assert_angle(nodes.child_angle(1), whorled.nth_angle(7));
+        assert_eq!(nodes.child_node(0), 4);
+        assert_eq!(nodes.child_node(1), 4);
+        assert_eq!(nodes.child_node(2), 5);
+
+        let random = Phyllotaxis::Random;
+        let nodes = LeafNodes::new(&random, 4, 8, 0);
+
+        assert_eq!(nodes.child_node(0), 1);
+        assert_eq!(nodes.child_node(1), 2);
+    }
+
+    #[test]
+    fn validate_checks_counts_and_angles() {
+        assert!(Phyllotaxis::Whorled { count: 1 }.validate().is_err());
+        assert!(Phyllotaxis::Spiral { angle: -1.0 }.validate().is_err());
+        assert_eq!(Phyllotaxis::Whorled { count: 2 }.validate(), Ok(()));
+    }
+
+    #[test]
+    fn spiral_defaults_to_the_golden_angle() {
+        assert_eq!(
+            ron::from_str::<Phyllotaxis>("Spiral()"),
+            Ok(Phyllotaxis::Spiral {
+                angle: GOLDEN_ANGLE
+            })
+        );
+    }
+}
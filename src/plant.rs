@@ -1,20 +1,37 @@
+use crate::colonization::*;
+use crate::curve::*;
+use crate::envelope::*;
+use crate::inheritance::*;
+use crate::level::*;
+use crate::lsystem::*;
+use crate::metrics::*;
+use crate::occlusion::*;
+use crate::organ::*;
+use crate::phyllotaxis::*;
+use crate::roots::*;
 use crate::shadow_render_resources::*;
+use crate::skeleton::*;
 use crate::sun::*;
+use crate::tropism::*;
 use bevy::{
+    asset::HandleId,
     prelude::*,
     reflect::TypeUuid,
     render::{
-        mesh::Indices,
         pipeline::{CullMode, PipelineDescriptor, PrimitiveState, RenderPipeline},
         render_graph::{base, RenderGraph, RenderResourcesNode},
         renderer::RenderResources,
         shader::ShaderStages,
     },
+    tasks::{AsyncComputeTaskPool, Task},
+    utils::HashMap,
 };
 use rand::prelude::*;
 use serde::{Deserialize, Serialize};
+use std::path::Path;
+use std::sync::Arc;
 
-#[derive(Serialize, Deserialize, TypeUuid)]
+#[derive(Serialize, Deserialize, TypeUuid, Clone)]
 #[uuid = "4192226a-c387-4719-a0e3-cbc936bf9961"]
 pub struct Genome {
     pub seed: Option<u64>,
@@ -24,121 +41,403 @@ pub struct Genome {
     pub radial_segments: usize,
     pub branch_length: f32,
     pub segments_per_branch: usize,
-    pub radius_sustain: f32,
+    pub radius_sustain: Param,
     pub leaf_start: usize,
-    pub leaf_density: f32,
+    pub leaf_density: Param,
     pub leaf_size: f32,
     pub leaf_length: f32,
     pub leaf_offset: f32,
     pub branch_decay: usize,
-    pub branch_bend: f32,
-    pub branch_sway: f32,
+    pub branch_bend: Param,
+    pub branch_sway: Param,
     pub branch_twist: f32,
+    #[serde(default)]
+    pub colonization: Option<Colonization>,
+    #[serde(default)]
+    pub growth: Growth,
+    /// Overrides for each split depth, starting with the trunk. Without any, branches
+    /// two or more splits from the trunk get half of an even `radial_segments`, as
+    /// they did before `levels` existed; list levels to set every count yourself.
+    #[serde(default)]
+    pub levels: Vec<LevelParams>,
+    /// Blossoms, fruit and other organs growing on the branches besides leaves.
+    #[serde(default)]
+    pub organs: Vec<OrganParams>,
+    /// How leaves and child branches are arranged around their branch.
+    #[serde(default)]
+    pub phyllotaxis: Phyllotaxis,
+    #[serde(default)]
+    pub tropism: Tropism,
+    /// The shape the crown is pruned to. Not used together with `colonization`,
+    /// which has an envelope of its own.
+    #[serde(default)]
+    pub envelope: Option<Envelope>,
+    /// Not used together with `colonization`, which keeps branches apart itself.
+    #[serde(default)]
+    pub avoidance: Option<Avoidance>,
+    #[serde(default)]
+    pub roots: Option<Roots>,
+    /// The fields this genome's own file sets, if it extends another genome, so they
+    /// can be applied again when the genome it extends changes.
+    #[serde(skip)]
+    pub patch: Option<GenomePatch>,
+}
+
+/// Calls `$then!` with every field of [`Genome`] besides `patch`, along with whether a
+/// genome has to set it or can leave it to its default, and the gene it's bred with.
+/// [`GenomePatch`] and breeding genomes are generated from it, so a new field only has
+/// to be listed here.
+macro_rules! genome_fields {
+    ($then:ident) => {
+        $then! {
+            seed: Option<u64>, default, SeedGene;
+            max_splits: usize, required, MAX_SPLITS;
+            branches_per_split: std::ops::Range<usize>, required, BRANCHES_PER_SPLIT;
+            starting_radius: f32, required, STARTING_RADIUS;
+            radial_segments: usize, required, RADIAL_SEGMENTS;
+            branch_length: f32, required, BRANCH_LENGTH;
+            segments_per_branch: usize, required, SEGMENTS_PER_BRANCH;
+            radius_sustain: Param, required, RADIUS_SUSTAIN;
+            leaf_start: usize, required, LEAF_START;
+            leaf_density: Param, required, LEAF_DENSITY;
+            leaf_size: f32, required, LEAF_SIZE;
+            leaf_length: f32, required, LEAF_LENGTH;
+            leaf_offset: f32, required, LEAF_OFFSET;
+            branch_decay: usize, required, BRANCH_DECAY;
+            branch_bend: Param, required, BRANCH_BEND;
+            branch_sway: Param, required, BRANCH_SWAY;
+            branch_twist: f32, required, BRANCH_TWIST;
+            colonization: Option<Colonization>, default, Nested;
+            growth: Growth, default, Nested;
+            levels: Vec<LevelParams>, default, Nested;
+            organs: Vec<OrganParams>, default, Nested;
+            phyllotaxis: Phyllotaxis, default, Nested;
+            tropism: Tropism, default, Nested;
+            envelope: Option<Envelope>, default, Nested;
+            avoidance: Option<Avoidance>, default, Nested;
+            roots: Option<Roots>, default, Nested;
+        }
+    };
+}
+
+pub(crate) use genome_fields;
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum GenomeError {
+    Missing {
+        field: &'static str,
+    },
+    NotFinite {
+        field: &'static str,
+    },
+    OutOfRange {
+        field: &'static str,
+        value: f64,
+        min: f64,
+        max: f64,
+    },
+    NotPositive {
+        field: &'static str,
+        value: f64,
+    },
+    EmptyRange {
+        field: &'static str,
+        start: usize,
+        end: usize,
+    },
+    EmptyCurve {
+        field: &'static str,
+    },
+    EmptyVolume {
+        field: &'static str,
+    },
+    Conflicting {
+        field: &'static str,
+        other: &'static str,
+    },
+    /// An error in one entry of `levels`, with its `field` relative to the entry.
+    Level {
+        index: usize,
+        error: Box<GenomeError>,
+    },
+    /// An error in one entry of `organs`, with its `field` relative to the entry.
+    Organ {
+        index: usize,
+        error: Box<GenomeError>,
+    },
+}
+
+impl std::fmt::Display for GenomeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Missing { field } => write!(
+                f,
+                "`{}` is missing, set it or `extends` a genome that does",
+                field
+            ),
+            Self::NotFinite { field } => write!(f, "`{}` must be a finite number", field),
+            Self::OutOfRange {
+                field,
+                value,
+                min,
+                max,
+            } => {
+                if max.is_finite() {
+                    write!(
+                        f,
+                        "`{}` is {}, but must be in the range {}..={}",
+                        field, value, min, max
+                    )
+                } else {
+                    write!(f, "`{}` is {}, but must be at least {}", field, value, min)
+                }
+            }
+            Self::NotPositive { field, value } => {
+                write!(f, "`{}` is {}, but must be greater than 0", field, value)
+            }
+            Self::Conflicting { field, other } => write!(
+                f,
+                "`{}` can't be used together with `{}`, leave one of them out",
+                field, other
+            ),
+            Self::EmptyVolume { field } => {
+                write!(f, "`{}` leaves no room to place points in", field)
+            }
+            Self::EmptyRange { field, start, end } => write!(
+                f,
+                "`{}` is ( start: {}, end: {} ), but start must not be greater than end",
+                field, start, end
+            ),
+            Self::EmptyCurve { field } => write!(f, "`{}` is a curve without any keys", field),
+            Self::Level { index, error } => write!(f, "in `levels[{}]`: {}", index, error),
+            Self::Organ { index, error } => write!(f, "in `organs[{}]`: {}", index, error),
+        }
+    }
+}
+
+impl std::error::Error for GenomeError {}
+
+pub(crate) fn check_count(
+    field: &'static str,
+    value: usize,
+    min: usize,
+) -> Result<(), GenomeError> {
+    if value < min {
+        Err(GenomeError::OutOfRange {
+            field,
+            value: value as f64,
+            min: min as f64,
+            max: f64::INFINITY,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn check_finite(field: &'static str, value: f32) -> Result<(), GenomeError> {
+    if value.is_finite() {
+        Ok(())
+    } else {
+        Err(GenomeError::NotFinite { field })
+    }
+}
+
+pub(crate) fn check_non_negative(field: &'static str, value: f32) -> Result<(), GenomeError> {
+    if !value.is_finite() {
+        Err(GenomeError::NotFinite { field })
+    } else if value < 0.0 {
+        Err(GenomeError::OutOfRange {
+            field,
+            value: value as f64,
+            min: 0.0,
+            max: f64::INFINITY,
+        })
+    } else {
+        Ok(())
+    }
+}
+
+pub(crate) fn check_positive(field: &'static str, value: f32) -> Result<(), GenomeError> {
+    if !value.is_finite() {
+        Err(GenomeError::NotFinite { field })
+    } else if value <= 0.0 {
+        Err(GenomeError::NotPositive {
+            field,
+            value: value as f64,
+        })
+    } else {
+        Ok(())
+    }
 }
 
 impl Genome {
-    pub fn generate_mesh(&self) -> Mesh {
-        let mut vertices = Vec::new();
-        let mut indices = Vec::new();
-        let mut sway = Vec::new();
-        let mut color = Vec::new();
-        let mut uv = Vec::new();
-        let mut material = Vec::new();
-        let mut leaves = 0;
-
-        let mut rng = if let Some(seed) = &self.seed {
-            rand::rngs::SmallRng::seed_from_u64(*seed)
-        } else {
-            rand::rngs::SmallRng::from_rng(thread_rng()).unwrap()
-        };
+    /// Checks every field against the range the generator can handle, so a bad
+    /// `.gno` file is rejected on load instead of panicking during mesh generation.
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        check_count("max_splits", self.max_splits, 2)?;
+
+        if self.branches_per_split.start > self.branches_per_split.end {
+            return Err(GenomeError::EmptyRange {
+                field: "branches_per_split",
+                start: self.branches_per_split.start,
+                end: self.branches_per_split.end,
+            });
+        }
 
-        let mut ctx = PlantContext {
-            vertices: &mut vertices,
-            indices: &mut indices,
-            sway: &mut sway,
-            color: &mut color,
-            uv: &mut uv,
-            material: &mut material,
-            leaves: &mut leaves,
-            rng: &mut rng,
-        };
+        check_non_negative("starting_radius", self.starting_radius)?;
+        check_count("radial_segments", self.radial_segments, 3)?;
+        check_non_negative("branch_length", self.branch_length)?;
+        check_count("segments_per_branch", self.segments_per_branch, 1)?;
+        self.radius_sustain
+            .validate("radius_sustain", check_non_negative)?;
+        self.leaf_density
+            .validate("leaf_density", check_non_negative)?;
+        check_non_negative("leaf_size", self.leaf_size)?;
+        check_non_negative("leaf_length", self.leaf_length)?;
+        check_positive("leaf_offset", self.leaf_offset)?;
+        self.branch_bend.validate("branch_bend", check_positive)?;
+        self.branch_sway.validate("branch_sway", check_positive)?;
+        check_non_negative("branch_twist", self.branch_twist)?;
+
+        if let Some(colonization) = &self.colonization {
+            colonization.validate()?;
+
+            if self.envelope.is_some() {
+                return Err(GenomeError::Conflicting {
+                    field: "envelope",
+                    other: "colonization",
+                });
+            }
 
-        let ring = Ring::generate(self.starting_radius, self.radial_segments, 0.0);
+            if self.avoidance.is_some() {
+                return Err(GenomeError::Conflicting {
+                    field: "avoidance",
+                    other: "colonization",
+                });
+            }
+        }
+
+        self.growth.validate()?;
+
+        for (index, level) in self.levels.iter().enumerate() {
+            level.validate().map_err(|error| GenomeError::Level {
+                index,
+                error: Box::new(error),
+            })?;
+        }
 
-        let start_loop = ctx.add_ring(ring, 0.0);
+        for (index, organ) in self.organs.iter().enumerate() {
+            organ.validate().map_err(|error| GenomeError::Organ {
+                index,
+                error: Box::new(error),
+            })?;
+        }
+
+        self.phyllotaxis.validate()?;
+        self.tropism.validate()?;
 
-        let branch = Branch {
-            start_loop,
-            ..Branch::generate(self)
+        if let Some(envelope) = &self.envelope {
+            envelope.validate()?;
+        }
+
+        if let Some(avoidance) = &self.avoidance {
+            avoidance.validate()?;
+        }
+
+        if let Some(roots) = &self.roots {
+            roots.validate()?;
+        }
+
+        Ok(())
+    }
+
+    /// Grows the skeleton by splitting branches recursively, `max_splits` times.
+    fn split(&self, rng: &mut rand::rngs::SmallRng, pull: Vec3) -> PlantSkeleton {
+        let mut skeleton = PlantSkeleton::default();
+        let mut ctx = SplitContext {
+            pull,
+            occupancy: self
+                .avoidance
+                .map(|avoidance| OccupancyGrid::new(avoidance.cell)),
         };
 
-        let mut branches = vec![branch];
+        let mut branches = vec![Branch::generate(self)];
 
         for _ in 0..self.max_splits {
-            for branch in std::mem::replace(&mut branches, Vec::new()) {
-                branches.append(&mut branch.generate_mesh(&mut ctx, self));
+            for branch in std::mem::take(&mut branches) {
+                branches.append(&mut branch.grow(&mut skeleton, rng, self, &mut ctx));
             }
         }
 
-        let mut mesh = Mesh::new(Default::default());
-        let mut normals = vec![Vec3::ZERO; vertices.len()];
+        skeleton
+    }
+}
 
-        for i in 0..indices.len() / 3 {
-            let i0 = indices[i * 3 + 0] as usize;
-            let i1 = indices[i * 3 + 1] as usize;
-            let i2 = indices[i * 3 + 2] as usize;
+/// A plant description asset that can be grown into a [`PlantSkeleton`].
+pub trait PlantGenerator: bevy::asset::Asset + Clone {
+    fn seed(&self) -> Option<u64>;
 
-            let v0 = vertices[i0];
-            let v1 = vertices[i1];
-            let v2 = vertices[i2];
+    /// `light` points toward the light the plant grows up under, such as the sun, in
+    /// the plant's own space.
+    fn generate_skeleton(&self, seed: u64, light: Vec3) -> PlantSkeleton;
 
-            let normal = (v1 - v0).cross(v2 - v0);
+    /// Whether the skeleton turns with the `light` it's grown under, plants that don't
+    /// grow the same under any light.
+    fn follows_light(&self) -> bool {
+        false
+    }
 
-            normals[i0] += normal;
-            normals[i1] += normal;
-            normals[i2] += normal;
-        }
+    /// The seed a plant instance grows from. A seed `explicit`ly given to the instance
+    /// wins, then a seed pinned in the asset so a fixed species looks the same
+    /// everywhere, and only then the `derived` one, such as from where it stands.
+    fn instance_seed(&self, explicit: Option<PlantSeed>, derived: PlantSeed) -> u64 {
+        explicit
+            .or_else(|| self.seed().map(PlantSeed))
+            .unwrap_or(derived)
+            .0
+    }
 
-        for normal in &mut normals {
-            *normal = normal.normalize();
-        }
+    fn growth(&self) -> Growth;
 
-        println!("Tree:");
-        println!(" tris: {}", indices.len() / 3);
-        println!(" verts: {}", vertices.len());
-        println!(" leaves: {}", leaves);
-        println!(" leaf_tris: {}", leaves * 2);
+    /// The fully grown plant, lit from straight above.
+    fn generate_mesh(&self, seed: u64, occlusion: Option<&OcclusionBake>) -> (Mesh, PlantMetrics) {
+        let growth = self.growth();
 
-        mesh.set_attribute(
-            Mesh::ATTRIBUTE_POSITION,
-            vertices
-                .into_iter()
-                .map(|v| v.into())
-                .collect::<Vec<[f32; 3]>>(),
-        );
-        mesh.set_attribute(
-            Mesh::ATTRIBUTE_UV_0,
-            uv.into_iter().map(|v| v.into()).collect::<Vec<[f32; 2]>>(),
-        );
-        mesh.set_attribute(
-            Mesh::ATTRIBUTE_NORMAL,
-            normals
-                .into_iter()
-                .map(|v| v.into())
-                .collect::<Vec<[f32; 3]>>(),
-        );
-        mesh.set_attribute("Plant_Material", material);
-        mesh.set_attribute("Plant_Sway", sway);
-        mesh.set_attribute(
-            "Vertex_Color",
-            color
-                .into_iter()
-                .map(|v| v.into())
-                .collect::<Vec<[f32; 4]>>(),
-        );
-        mesh.set_indices(Some(Indices::U32(indices)));
+        self.generate_skeleton(seed, Vec3::Y)
+            .grown(growth.max_age, &growth)
+            .generate_mesh(occlusion)
+    }
+}
+
+impl PlantGenerator for Genome {
+    fn seed(&self) -> Option<u64> {
+        self.seed
+    }
+
+    fn growth(&self) -> Growth {
+        self.growth
+    }
+
+    fn follows_light(&self) -> bool {
+        self.tropism.phototropism != 0.0 && self.tropism.light.is_none()
+    }
+
+    fn generate_skeleton(&self, seed: u64, light: Vec3) -> PlantSkeleton {
+        let mut rng = rand::rngs::SmallRng::seed_from_u64(seed);
+        let pull = self.tropism.pull(light);
+
+        let mut skeleton = if let Some(colonization) = &self.colonization {
+            colonization.generate_skeleton(self, &mut rng, pull)
+        } else {
+            self.split(&mut rng, pull)
+        };
 
-        mesh
+        // roots come last so they leave the crown of a genome without them as it was
+        if let Some(roots) = &self.roots {
+            roots.grow(&mut skeleton, &mut rng);
+        }
+
+        skeleton
     }
 }
 
@@ -173,9 +472,9 @@ impl Ring {
         }
     }
 
-    pub fn rotate(&mut self, rot: Vec3) {
+    pub fn rotate(&mut self, rot: Quat) {
         for v in &mut self.verts {
-            *v = rotate(*v, rot);
+            *v = rot * *v;
         }
     }
 }
@@ -188,12 +487,11 @@ pub struct PlantContext<'a> {
     pub uv: &'a mut Vec<Vec2>,
     pub material: &'a mut Vec<u32>,
     pub leaves: &'a mut usize,
-    pub rng: &'a mut rand::rngs::SmallRng,
 }
 
 impl PlantContext<'_> {
     pub fn add_ring(&mut self, ring: Ring, v: f32) -> Vec<u32> {
-        (0..ring.verts.len()).into_iter().for_each(|i| {
+        (0..ring.verts.len()).for_each(|i| {
             let u = (1.0 - i as f32 / ring.verts.len() as f32 * 2.0).abs() * ring.radius;
 
             self.uv.push(Vec2::new(u, v));
@@ -209,6 +507,73 @@ impl PlantContext<'_> {
             })
             .collect()
     }
+
+    pub fn bridge_loops(&mut self, loop_a: &[u32], loop_b: &[u32]) {
+        if loop_a.len() == loop_b.len() {
+            for a in 0..loop_a.len() {
+                let a_next = (a + 1) % loop_a.len();
+                let b = a;
+                let b_next = (b + 1) % loop_a.len();
+
+                self.indices.push(loop_b[b]);
+                self.indices.push(loop_a[a]);
+                self.indices.push(loop_a[a_next]);
+
+                self.indices.push(loop_a[a_next]);
+                self.indices.push(loop_b[b_next]);
+                self.indices.push(loop_b[b]);
+            }
+        } else if loop_a.len() * 2 == loop_b.len() {
+            for a in 0..loop_a.len() {
+                let a_1 = (a + 1) % loop_a.len();
+                let b = a * 2;
+                let b_1 = (b + 1) % loop_b.len();
+                let b_2 = (b + 2) % loop_b.len();
+
+                self.indices.push(loop_b[b]);
+                self.indices.push(loop_a[a]);
+                self.indices.push(loop_b[b_1]);
+
+                self.indices.push(loop_a[a]);
+                self.indices.push(loop_b[b_1]);
+                self.indices.push(loop_a[a_1]);
+
+                self.indices.push(loop_a[a_1]);
+                self.indices.push(loop_b[b_1]);
+                self.indices.push(loop_b[b_2]);
+            }
+        } else {
+            // walk around both loops at once, always advancing the one that lags behind
+            let (mut a, mut b) = (0, 0);
+
+            while a < loop_a.len() || b < loop_b.len() {
+                let next_a = (a + 1) as f32 / loop_a.len() as f32;
+                let next_b = (b + 1) as f32 / loop_b.len() as f32;
+
+                if b == loop_b.len() || (a < loop_a.len() && next_a <= next_b) {
+                    self.indices.push(loop_b[b % loop_b.len()]);
+                    self.indices.push(loop_a[a]);
+                    self.indices.push(loop_a[(a + 1) % loop_a.len()]);
+
+                    a += 1;
+                } else {
+                    self.indices.push(loop_a[a % loop_a.len()]);
+                    self.indices.push(loop_b[(b + 1) % loop_b.len()]);
+                    self.indices.push(loop_b[b]);
+
+                    b += 1;
+                }
+            }
+        }
+    }
+}
+
+/// State shared by every branch while a genome splits into a skeleton.
+pub struct SplitContext {
+    /// The [`Tropism::pull`] every segment turns toward.
+    pub pull: Vec3,
+    /// Where wood has grown so far, if the genome avoids it.
+    pub occupancy: Option<OccupancyGrid>,
 }
 
 pub struct Branch {
@@ -222,12 +587,26 @@ pub struct Branch {
     pub bend: Vec3,
     pub segments: usize,
     pub radial_segments: usize,
-    pub start_loop: Vec<u32>,
+    pub parent: Option<usize>,
+    pub parent_frame: usize,
     pub sway: f32,
+    /// How far tropisms have turned this branch and the branches it grows out of,
+    /// applied on top of `direction` and `bend`.
+    pub tropism: Quat,
+    /// Whether this branch or one it grows out of has grown into the genome's
+    /// [`Envelope`], after which it's held to it.
+    pub in_envelope: bool,
+    /// Leaf node the branch's [`Phyllotaxis`] counts on from, so its leaves and
+    /// children carry on from where it grew out of its parent.
+    pub node: usize,
+}
+
+fn euler(rot: Vec3) -> Quat {
+    Quat::from_rotation_ypr(rot.y, rot.x, rot.z)
 }
 
 fn rotate(vec: Vec3, rot: Vec3) -> Vec3 {
-    Quat::from_rotation_ypr(rot.y, rot.x, rot.z) * vec
+    euler(rot) * vec
 }
 
 fn lerp(a: f32, b: f32, mix: f32) -> f32 {
@@ -236,121 +615,228 @@ fn lerp(a: f32, b: f32, mix: f32) -> f32 {
 
 impl Branch {
     pub fn generate(genome: &Genome) -> Self {
+        let level = genome.level(0, 0.0);
+
         Self {
             split: 0,
             branch_decay: 0,
             start_radius: genome.starting_radius,
-            end_radius: genome.starting_radius * genome.radius_sustain,
-            length: genome.branch_length,
+            end_radius: genome.starting_radius
+                * genome
+                    .radius_sustain
+                    .sample(CurvePosition::new(genome, 0, 0.0)),
+            length: level.length,
             start: Vec3::ZERO,
             direction: Vec3::ZERO,
             bend: Vec3::ZERO,
             segments: genome.segments_per_branch,
-            radial_segments: genome.radial_segments,
-            start_loop: Vec::new(),
+            radial_segments: level.radial_segments,
+            parent: None,
+            parent_frame: 0,
             sway: 0.0,
+            tropism: Quat::IDENTITY,
+            in_envelope: false,
+            node: 0,
         }
     }
 
-    pub fn generate_mesh(&self, ctx: &mut PlantContext<'_>, genome: &Genome) -> Vec<Branch> {
+    /// Adds this branch to `skeleton` and returns the child branches that split off its end.
+    ///
+    /// A branch pruned by the genome's [`Envelope`] ends early without children, or
+    /// isn't added at all if it's pruned before its first segment.
+    pub fn grow(
+        &self,
+        skeleton: &mut PlantSkeleton,
+        rng: &mut rand::rngs::SmallRng,
+        genome: &Genome,
+        ctx: &mut SplitContext,
+    ) -> Vec<Branch> {
         let segment_length = 1.0 / self.segments as f32 * self.length;
         let mut pos = self.start;
         let mut bend = self.direction;
-
-        let mut prev_loop = self.start_loop.clone();
+        let mut turn = self.tropism;
+        let mut pruned = false;
+
+        let mut inside = self.in_envelope
+            || genome
+                .envelope
+                .as_ref()
+                .is_some_and(|envelope| envelope.shape.contains(pos));
+
+        // the index this branch is about to be added at
+        let index = skeleton.branches.len();
+        let own = [Some(index), self.parent]
+            .iter()
+            .flatten()
+            .copied()
+            .collect::<Vec<_>>();
+
+        let mut frames = vec![SegmentFrame {
+            position: self.start,
+            rotation: turn * euler(bend),
+            radius: self.start_radius,
+            sway: self.sway,
+        }];
+        let mut leaves = Vec::new();
+        let mut nodes = LeafNodes::new(
+            &genome.phyllotaxis,
+            self.segments,
+            self.radial_segments,
+            self.node,
+        );
 
         for segment in 1..=self.segments {
             let segment_lerp = segment as f32 / self.segments as f32;
 
             bend += self.bend * (1.0 / self.segments as f32);
 
-            pos += rotate(Vec3::Y, bend) * segment_length;
+            if ctx.pull != Vec3::ZERO {
+                let direction = turn * rotate(Vec3::Y, bend);
 
-            let radius = lerp(self.end_radius, self.start_radius, segment_lerp);
-            let sway = self.sway + self.length * segment_lerp;
-
-            let mut ring = Ring::generate(radius, self.radial_segments, sway);
+                turn = Tropism::turn(ctx.pull, direction, segment_length) * turn;
+            }
 
-            ring.rotate(bend);
-            ring.translate(pos);
+            if let (Some(occupancy), Some(avoidance)) = (&ctx.occupancy, &genome.avoidance) {
+                let away = occupancy.repulsion(pos, &own, self.start);
 
-            if self.split >= genome.leaf_start {
-                for vert in &ring.verts {
-                    if ctx.rng.gen_range(0.0..1.0)
-                        > genome.leaf_density / self.segments as f32 / self.radial_segments as f32
-                    {
-                        continue;
-                    }
+                if away != Vec3::ZERO {
+                    let direction = turn * rotate(Vec3::Y, bend);
 
-                    let mut o = || {
-                        let r = radius.max(0.01) * genome.leaf_offset;
+                    turn =
+                        Tropism::turn(away * avoidance.strength, direction, segment_length) * turn;
+                }
+            }
 
-                        ctx.rng.gen_range(-r..r)
-                    };
+            if let Some(envelope) = genome.envelope.as_ref().filter(|_| inside) {
+                let direction = turn * rotate(Vec3::Y, bend);
 
-                    let diff = (*vert + Vec3::new(o(), o(), o())) - pos;
-                    let up = Vec3::new(
-                        ctx.rng.gen_range(-3.14..3.14),
-                        1.0,
-                        ctx.rng.gen_range(-3.14..3.14),
-                    );
-
-                    let forward = diff.normalize();
-                    let right = up.cross(forward).normalize();
-                    let up = forward.cross(right);
-
-                    let rot = Quat::from_rotation_mat3(&Mat3::from_cols(right, up, forward));
-
-                    let leaf = Leaf {
-                        pos: *vert,
-                        rot,
-                        sway,
-                        size: genome.leaf_size,
-                        length: genome.leaf_length,
-                    };
+                if !envelope.shape.contains(pos + direction * segment_length) {
+                    match envelope.response {
+                        EnvelopeResponse::Prune => {
+                            pruned = true;
+                            break;
+                        }
+                        EnvelopeResponse::BendBack { strength } => {
+                            let inward = envelope.shape.inward(pos) * strength;
 
-                    leaf.generate_mesh(ctx);
+                            turn = Tropism::turn(inward, direction, segment_length) * turn;
+                        }
+                    }
                 }
             }
 
-            let mut indices = ctx.add_ring(ring, sway);
-            let len = indices.len();
-            indices.rotate_left((bend.y / std::f32::consts::TAU * len as f32) as usize % len);
+            pos += turn * rotate(Vec3::Y, bend) * segment_length;
+            inside = inside
+                || genome
+                    .envelope
+                    .as_ref()
+                    .is_some_and(|envelope| envelope.shape.contains(pos));
 
-            self.bridge_loops(ctx, &indices, &prev_loop);
+            let frame = SegmentFrame {
+                position: pos,
+                rotation: turn * euler(bend),
+                radius: lerp(self.end_radius, self.start_radius, segment_lerp),
+                sway: self.sway + self.length * segment_lerp,
+            };
 
-            prev_loop = indices;
+            if self.split >= genome.leaf_start {
+                nodes.place(
+                    rng,
+                    &genome.level(self.split, pos.y),
+                    &frames[frames.len() - 1],
+                    &frame,
+                    &mut leaves,
+                );
+            }
+
+            frames.push(frame);
         }
 
-        let mut splits = ctx
-            .rng
-            .gen_range(genome.branches_per_split.start..=genome.branches_per_split.end);
+        if frames.len() < 2 {
+            return Vec::new();
+        }
+
+        if let Some(occupancy) = &mut ctx.occupancy {
+            occupancy.insert(index, &frames);
+        }
+
+        // the last split grows the branches nothing splits off of
+        let tip = self.split + 1 == genome.max_splits;
+        let organs = genome.place_organs(rng, self.split, &frames, self.radial_segments, tip);
+        let last = frames[frames.len() - 1];
+
+        skeleton.add_branch(SkeletonBranch {
+            parent: self.parent,
+            parent_frame: self.parent_frame,
+            children: Vec::new(),
+            split: self.split,
+            start: self.start,
+            direction: self.tropism * rotate(Vec3::Y, self.direction),
+            bend: self.bend,
+            start_radius: self.start_radius,
+            end_radius: last.radius,
+            length: last.sway - self.sway,
+            radial_segments: self.radial_segments,
+            sway: self.sway,
+            frames,
+            leaves,
+            organs,
+            root: false,
+        });
+
+        if pruned {
+            return Vec::new();
+        }
+
+        let branches = genome.level(self.split, pos.y).branches;
+        let mut splits = rng.gen_range(branches.start..=branches.end);
 
         splits = splits.saturating_sub(self.branch_decay).max(1);
 
+        // the children's parameters are sampled where they start
+        let position = CurvePosition::new(genome, self.split + 1, pos.y);
+        let level = genome.level(self.split + 1, pos.y);
+        let branch_sway = genome.branch_sway.sample(position);
+        let radius_sustain = genome.radius_sustain.sample(position);
+
         (0..splits)
-            .into_iter()
             .map(|i| {
                 let mut new_bend = self.bend;
                 let mut new_direction = bend;
 
-                if self.split == 0 {
+                if genome.phyllotaxis != Phyllotaxis::Random {
+                    // children follow on from each other like leaves, counting on
+                    // from the leaf nodes of their own parent
+                    let angle = nodes.child_angle(i);
+
+                    new_direction.y += if self.split == 0 {
+                        angle
+                    } else {
+                        // away from the trunk the full turn is squeezed into the fan
+                        // of `branch_sway` around the parent's heading
+                        let centered = (angle + std::f32::consts::FRAC_PI_2)
+                            .rem_euclid(std::f32::consts::TAU)
+                            - std::f32::consts::PI;
+
+                        centered / std::f32::consts::PI * branch_sway
+                    };
+                } else if self.split == 0 {
                     let angle = 1.0 / splits as f32 * std::f32::consts::TAU;
                     let dir = i as f32 / splits as f32 * std::f32::consts::TAU;
 
-                    new_direction.y += dir + ctx.rng.gen_range(0.0..angle * 0.8);
+                    new_direction.y += dir + rng.gen_range(0.0..angle * 0.8);
                 } else {
-                    let angle = 1.0 / splits as f32 * genome.branch_sway * 0.8;
-                    let dir = (i as f32 / splits as f32 - 0.5) * genome.branch_sway * 2.0;
+                    let angle = 1.0 / splits as f32 * branch_sway * 0.8;
+                    let dir = (i as f32 / splits as f32 - 0.5) * branch_sway * 2.0;
 
-                    new_direction.y += dir + ctx.rng.gen_range(-angle..angle);
+                    new_direction.y += dir + rng.gen_range(-angle..angle);
                 }
 
-                if genome.branch_twist != 0.0 {
-                    new_bend.z += ctx.rng.gen_range(-genome.branch_twist..genome.branch_twist);
+                if level.twist != 0.0 {
+                    new_bend.z += rng.gen_range(-level.twist..level.twist);
                 }
 
-                let bend = ctx.rng.gen_range(0.0..genome.branch_bend);
+                let bend = rng.gen_range(0.0..level.bend);
 
                 new_direction.x += bend * (2.0 / 3.0);
                 new_bend.x += bend * (1.0 / 3.0);
@@ -358,13 +844,7 @@ impl Branch {
                 let end_radius = if self.split == genome.max_splits - 2 {
                     0.0
                 } else {
-                    self.end_radius * genome.radius_sustain
-                };
-
-                let radial_segments = if self.split == 1 && self.radial_segments % 2 == 0 {
-                    self.radial_segments / 2
-                } else {
-                    self.radial_segments
+                    self.end_radius * radius_sustain
                 };
 
                 Branch {
@@ -373,55 +853,24 @@ impl Branch {
                     start: pos,
                     start_radius: self.end_radius,
                     end_radius,
-                    start_loop: prev_loop.clone(),
+                    parent: Some(index),
+                    parent_frame: self.segments,
                     direction: new_direction,
                     bend: new_bend,
                     sway: self.sway + self.length,
-                    radial_segments,
+                    length: level.length,
+                    radial_segments: level.radial_segments,
+                    tropism: turn,
+                    in_envelope: inside,
+                    node: nodes.child_node(i),
                     ..Branch::generate(genome)
                 }
             })
             .collect()
     }
-
-    pub fn bridge_loops(&self, ctx: &mut PlantContext<'_>, loop_a: &Vec<u32>, loop_b: &Vec<u32>) {
-        if loop_a.len() == loop_b.len() {
-            for a in 0..loop_a.len() {
-                let a_next = (a + 1) % loop_a.len();
-                let b = a;
-                let b_next = (b + 1) % loop_a.len();
-
-                ctx.indices.push(loop_b[b]);
-                ctx.indices.push(loop_a[a]);
-                ctx.indices.push(loop_a[a_next]);
-
-                ctx.indices.push(loop_a[a_next]);
-                ctx.indices.push(loop_b[b_next]);
-                ctx.indices.push(loop_b[b]);
-            }
-        } else if loop_a.len() * 2 == loop_b.len() {
-            for a in 0..loop_a.len() {
-                let a_1 = (a + 1) % loop_a.len();
-                let b = a * 2;
-                let b_1 = (b + 1) % loop_b.len();
-                let b_2 = (b + 2) % loop_b.len();
-
-                ctx.indices.push(loop_b[b]);
-                ctx.indices.push(loop_a[a]);
-                ctx.indices.push(loop_b[b_1]);
-
-                ctx.indices.push(loop_a[a]);
-                ctx.indices.push(loop_b[b_1]);
-                ctx.indices.push(loop_a[a_1]);
-
-                ctx.indices.push(loop_a[a_1]);
-                ctx.indices.push(loop_b[b_1]);
-                ctx.indices.push(loop_b[b_2]);
-            }
-        }
-    }
 }
 
+#[derive(Clone)]
 pub struct Leaf {
     pub pos: Vec3,
     pub rot: Quat,
@@ -431,6 +880,37 @@ pub struct Leaf {
 }
 
 impl Leaf {
+    /// A leaf sticking out of the branch surface at `vert` in a random direction.
+    pub fn random(
+        rng: &mut rand::rngs::SmallRng,
+        level: &Level,
+        vert: Vec3,
+        frame: &SegmentFrame,
+    ) -> Self {
+        let mut o = || {
+            let r = frame.radius.max(0.01) * level.leaf_offset;
+
+            rng.gen_range(-r..r)
+        };
+
+        let diff = (vert + Vec3::new(o(), o(), o())) - frame.position;
+        let up = Vec3::new(rng.gen_range(-3.14..3.14), 1.0, rng.gen_range(-3.14..3.14));
+
+        let forward = diff.normalize();
+        let right = up.cross(forward).normalize();
+        let up = forward.cross(right);
+
+        let rot = Quat::from_rotation_mat3(&Mat3::from_cols(right, up, forward));
+
+        Self {
+            pos: vert,
+            rot,
+            sway: frame.sway,
+            size: level.leaf_size,
+            length: level.leaf_length,
+        }
+    }
+
     pub fn generate_mesh(&self, ctx: &mut PlantContext<'_>) -> Vec<u32> {
         *ctx.leaves += 1;
 
@@ -481,22 +961,379 @@ impl Leaf {
 #[derive(Default, RenderResources, TypeUuid)]
 #[uuid = "5739c0cc-eefb-4e41-b2fc-0e8d937fcff7"]
 pub struct PlantMaterial {
-    pub time: f32,
-    pub growth: f32,
     pub texture: Handle<Texture>,
     pub leaf_front: Handle<Texture>,
+    pub blossom: Handle<Texture>,
+    /// Shared by fruit, cones and seed pods, tinted by the organ color.
+    pub fruit: Handle<Texture>,
 }
 
 impl PlantMaterial {
-    pub fn new(texture: Handle<Texture>, leaf_front: Handle<Texture>) -> Self {
+    pub fn new(
+        texture: Handle<Texture>,
+        leaf_front: Handle<Texture>,
+        blossom: Handle<Texture>,
+        fruit: Handle<Texture>,
+    ) -> Self {
         Self {
             texture,
             leaf_front,
-            ..Default::default()
+            blossom,
+            fruit,
         }
     }
 }
 
+/// Mesh handles for each level of detail, swapped in by distance to the camera.
+pub struct PlantLod {
+    pub meshes: Vec<Handle<Mesh>>,
+    pub current: usize,
+}
+
+impl PlantLod {
+    /// Camera distance at which each level after the first takes over.
+    pub const DISTANCES: [f32; 3] = [20.0, 40.0, 80.0];
+    pub const LEVELS: usize = Self::DISTANCES.len() + 1;
+
+    pub fn new(meshes: Vec<Handle<Mesh>>) -> Self {
+        Self { meshes, current: 0 }
+    }
+
+    pub fn level(distance: f32) -> usize {
+        Self::DISTANCES
+            .iter()
+            .take_while(|&&d| distance >= d)
+            .count()
+    }
+}
+
+/// The asset, effective seed and light a plant was grown from, which together decide
+/// the meshes of the fully grown plant.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+pub struct PlantInstance {
+    pub asset: HandleId,
+    pub seed: u64,
+    /// The direction of the light the plant grew toward in [`PlantInstance::LIGHT_STEPS`]
+    /// steps, so plants lit nearly alike share meshes. `None` for plants the light
+    /// doesn't turn.
+    pub light: Option<[i32; 3]>,
+}
+
+impl PlantInstance {
+    pub const LIGHT_STEPS: f32 = 16.0;
+
+    pub fn new(asset: HandleId, seed: u64, light: Option<Vec3>) -> Self {
+        Self {
+            asset,
+            seed,
+            light: light.map(|light| {
+                let light = (light.normalize_or_zero() * Self::LIGHT_STEPS).round();
+
+                [light.x as i32, light.y as i32, light.z as i32]
+            }),
+        }
+    }
+
+    /// The light the plant grows toward, the direction it was given rounded to the
+    /// steps it's told apart by.
+    pub fn light(&self) -> Option<Vec3> {
+        self.light
+            .map(|[x, y, z]| Vec3::new(x as f32, y as f32, z as f32).normalize_or_zero())
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq, Hash, Debug)]
+struct PlantMeshKey {
+    instance: PlantInstance,
+    lod: usize,
+}
+
+struct CachedMesh {
+    /// Weak, the plants using the mesh keep it alive.
+    mesh: Handle<Mesh>,
+    /// Metrics of the full detail mesh.
+    metrics: PlantMetrics,
+}
+
+/// Meshes of fully grown plants, shared by every plant grown from the same asset
+/// with the same seed under the same light, see [`PlantInstance`].
+///
+/// Entries are dropped when the last plant using them is gone and the mesh is freed.
+#[derive(Default)]
+pub struct PlantMeshCache {
+    meshes: HashMap<PlantMeshKey, CachedMesh>,
+}
+
+impl PlantMeshCache {
+    /// Every level of detail of `instance` and its metrics, if they're all cached.
+    pub fn get(
+        &self,
+        instance: PlantInstance,
+        meshes: &Assets<Mesh>,
+    ) -> Option<(Vec<Handle<Mesh>>, PlantMetrics)> {
+        let lods = (0..PlantLod::LEVELS)
+            .map(|lod| {
+                let cached = self.meshes.get(&PlantMeshKey { instance, lod })?;
+
+                if meshes.contains(&cached.mesh) {
+                    Some(meshes.get_handle(&cached.mesh))
+                } else {
+                    None
+                }
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        let metrics = self.meshes[&PlantMeshKey { instance, lod: 0 }]
+            .metrics
+            .clone();
+
+        Some((lods, metrics))
+    }
+
+    pub fn insert(
+        &mut self,
+        instance: PlantInstance,
+        lods: &[Handle<Mesh>],
+        metrics: &PlantMetrics,
+    ) {
+        for (lod, mesh) in lods.iter().enumerate() {
+            self.meshes.insert(
+                PlantMeshKey { instance, lod },
+                CachedMesh {
+                    mesh: mesh.clone_weak(),
+                    metrics: metrics.clone(),
+                },
+            );
+        }
+    }
+
+    /// The cached meshes of `instance`, or `new` added to `meshes` and cached if there
+    /// are none.
+    pub fn share(
+        &mut self,
+        instance: PlantInstance,
+        new: PlantMeshes,
+        meshes: &mut Assets<Mesh>,
+    ) -> (Vec<Handle<Mesh>>, PlantMetrics) {
+        if let Some(cached) = self.get(instance, meshes) {
+            return cached;
+        }
+
+        let lods = new
+            .lods
+            .into_iter()
+            .map(|mesh| meshes.add(mesh))
+            .collect::<Vec<_>>();
+
+        self.insert(instance, &lods, &new.metrics);
+
+        (lods, new.metrics)
+    }
+
+    /// Forgets every mesh grown from `asset`, used when the asset changes.
+    pub fn evict(&mut self, asset: HandleId) {
+        self.meshes.retain(|key, _| key.instance.asset != asset);
+    }
+}
+
+/// Drops cache entries whose meshes were freed.
+pub fn plant_mesh_cache_system(
+    mut cache: ResMut<PlantMeshCache>,
+    mut events: EventReader<AssetEvent<Mesh>>,
+) {
+    for event in events.iter() {
+        if let AssetEvent::Removed { handle } = event {
+            cache.meshes.retain(|_, cached| cached.mesh.id != handle.id);
+        }
+    }
+}
+
+/// How old a plant is in seconds, insert it when spawning a plant to start it at a
+/// different age.
+#[derive(Clone, Copy, Debug)]
+pub struct PlantAge(pub f32);
+
+impl Default for PlantAge {
+    /// A second old, so a freshly planted seedling has a stem to draw.
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// The fully grown skeleton of a plant, which is re-meshed as the plant ages.
+pub struct PlantGrowth {
+    /// Shared with the tasks meshing the plant in the background.
+    pub skeleton: Arc<PlantSkeleton>,
+    pub growth: Growth,
+    /// The age the current meshes were generated at, or are being generated at.
+    pub meshed_age: Option<f32>,
+    /// Baked into the meshes once the plant is fully grown.
+    pub occlusion: Option<OcclusionBake>,
+}
+
+impl PlantGrowth {
+    /// Seconds of growth between re-meshing.
+    pub const INTERVAL: f32 = 0.25;
+
+    pub fn new(skeleton: PlantSkeleton, growth: Growth, occlusion: Option<OcclusionBake>) -> Self {
+        Self {
+            skeleton: Arc::new(skeleton),
+            growth,
+            meshed_age: None,
+            occlusion,
+        }
+    }
+
+    /// Whether the plant has aged enough since it was last meshed to be meshed again,
+    /// the final age is always meshed so fully grown plants end up exact.
+    pub fn needs_mesh(&self, age: f32) -> bool {
+        let age = age.min(self.growth.max_age);
+
+        match self.meshed_age {
+            Some(meshed) => {
+                age != meshed
+                    && ((age - meshed).abs() >= Self::INTERVAL || age == self.growth.max_age)
+            }
+            None => true,
+        }
+    }
+
+    /// Whether the current meshes are of the fully grown plant, after which they
+    /// never change and can be shared through the [`PlantMeshCache`].
+    pub fn fully_grown(&self) -> bool {
+        self.meshed_age == Some(self.growth.max_age)
+    }
+
+    pub fn generate_meshes(&mut self, age: f32, levels: usize) -> PlantMeshes {
+        let age = age.min(self.growth.max_age);
+        self.meshed_age = Some(age);
+
+        PlantMeshes::generate(&self.skeleton, &self.growth, age, levels, self.occlusion)
+    }
+
+    /// Like [`PlantGrowth::generate_meshes`], but on the task pool.
+    pub fn spawn_meshes(
+        &mut self,
+        task_pool: &AsyncComputeTaskPool,
+        age: f32,
+        levels: usize,
+    ) -> Task<PlantMeshes> {
+        let age = age.min(self.growth.max_age);
+        self.meshed_age = Some(age);
+
+        let skeleton = self.skeleton.clone();
+        let growth = self.growth;
+        let occlusion = self.occlusion;
+
+        task_pool
+            .spawn(async move { PlantMeshes::generate(&skeleton, &growth, age, levels, occlusion) })
+    }
+}
+
+/// Meshes for every level of detail of a plant at one age, along with the metrics
+/// of the full detail mesh.
+pub struct PlantMeshes {
+    pub lods: Vec<Mesh>,
+    pub metrics: PlantMetrics,
+}
+
+impl PlantMeshes {
+    /// Only a fully grown plant is baked with `occlusion`.
+    pub fn generate(
+        skeleton: &PlantSkeleton,
+        growth: &Growth,
+        age: f32,
+        levels: usize,
+        occlusion: Option<OcclusionBake>,
+    ) -> Self {
+        let occlusion = occlusion.filter(|_| age >= growth.max_age);
+        let (lods, metrics) = skeleton
+            .grown(age, growth)
+            .generate_lods(levels, occlusion.as_ref());
+
+        Self { lods, metrics }
+    }
+}
+
+/// A plant being grown on the task pool, it has no mesh until the task finishes.
+pub struct PlantTask(pub Task<(PlantGrowth, PlantMeshes)>);
+
+impl PlantTask {
+    /// Finished plants given their meshes each frame, so a batch finishing at once
+    /// doesn't stall a single frame uploading them all.
+    pub const BUDGET: usize = 4;
+
+    /// Only the organs in season at `season` are grown, tropisms turn toward `light`
+    /// and the fully grown plant is baked with `occlusion`.
+    pub fn spawn<G: PlantGenerator>(
+        task_pool: &AsyncComputeTaskPool,
+        generator: &G,
+        seed: u64,
+        age: f32,
+        season: Season,
+        light: Vec3,
+        occlusion: PlantOcclusion,
+    ) -> Self {
+        let generator = generator.clone();
+
+        Self(task_pool.spawn(async move {
+            let skeleton = generator.generate_skeleton(seed, light).in_season(season.0);
+            let mut growth = PlantGrowth::new(skeleton, generator.growth(), occlusion.0);
+            let meshes = growth.generate_meshes(age, PlantLod::LEVELS);
+
+            (growth, meshes)
+        }))
+    }
+}
+
+/// A plant being re-meshed at a new age on the task pool, it keeps its old meshes
+/// until the task finishes.
+pub struct PlantGrowthTask(pub Task<PlantMeshes>);
+
+/// Seed for everything random about the world, such as where plants are placed
+/// and which seed each of them grows from.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct WorldSeed(pub u64);
+
+impl WorldSeed {
+    /// Reads the seed from the `WORLD_SEED` environment variable, or picks a random one.
+    pub fn from_env() -> Self {
+        match std::env::var("WORLD_SEED")
+            .ok()
+            .and_then(|s| s.parse().ok())
+        {
+            Some(seed) => Self(seed),
+            None => Self(thread_rng().gen()),
+        }
+    }
+}
+
+/// The seed a single plant grows from, even when its asset pins one. Plants without
+/// one grow from the pinned seed or a seed derived from their position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct PlantSeed(pub u64);
+
+impl PlantSeed {
+    /// Derives a seed from the world seed and where the plant stands on the ground,
+    /// positions are rounded to centimeters so they survive a round trip through text.
+    pub fn from_position(world: WorldSeed, position: Vec3) -> Self {
+        let x = (position.x * 100.0).round() as i64 as u64;
+        let z = (position.z * 100.0).round() as i64 as u64;
+
+        Self(mix(mix(world.0, x), z))
+    }
+}
+
+/// Combines `value` into `hash` with a splitmix64 finalizer, so nearby positions
+/// end up with unrelated seeds.
+pub(crate) fn mix(hash: u64, value: u64) -> u64 {
+    let mut z = hash ^ value.wrapping_add(0x9e3779b97f4a7c15);
+
+    z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+    z ^ (z >> 31)
+}
+
 #[derive(Bundle)]
 pub struct PlantBundle {
     pub material: PlantMaterial,
@@ -528,12 +1365,6 @@ impl Default for PlantBundle {
     }
 }
 
-pub fn plant_material_system(time: Res<Time>, mut query: Query<&mut PlantMaterial>) {
-    for mut plant_material in query.iter_mut() {
-        plant_material.time += time.delta_seconds();
-    }
-}
-
 pub struct GenomeLoader;
 
 impl bevy::asset::AssetLoader for GenomeLoader {
@@ -543,15 +1374,47 @@ impl bevy::asset::AssetLoader for GenomeLoader {
         load_context: &'a mut bevy::asset::LoadContext,
     ) -> bevy::utils::BoxedFuture<'a, Result<(), anyhow::Error>> {
         Box::pin(async move {
-            let asset = ron::de::from_bytes::<Genome>(bytes).map_err(|e| {
-                anyhow::Error::msg(format!(
-                    "'{}': {}",
-                    load_context.path().to_string_lossy(),
-                    e
-                ))
-            })?;
+            let path = load_context.path().to_owned();
+            let error =
+                |e: anyhow::Error| anyhow::Error::msg(format!("'{}': {}", path.display(), e));
+
+            let patch = GenomePatch::from_bytes(bytes).map_err(error)?;
+
+            let mut chain = ExtendsChain::default();
+            chain.visit(&path).map_err(error)?;
+
+            // the parent is resolved here from its file, the dependency only makes sure
+            // it's loaded so its changes reach this genome, `GenomeParents` keeps it
+            // loaded
+            let mut resolved = patch.clone();
+
+            while let Some(extends) = resolved.extends.clone() {
+                chain.visit(Path::new(&extends)).map_err(error)?;
 
-            load_context.set_default_asset(bevy::asset::LoadedAsset::new(asset));
+                let bytes = load_context
+                    .read_asset_bytes(&extends)
+                    .await
+                    .map_err(|e| error(e.into()))?;
+                let parent = GenomePatch::from_bytes(&bytes)
+                    .map_err(|e| error(anyhow::anyhow!("'{}': {}", extends, e)))?;
+
+                resolved = resolved.over(parent);
+            }
+
+            let mut asset = resolved.into_genome().map_err(|e| error(e.into()))?;
+            let extends = patch.extends.clone();
+
+            if extends.is_some() {
+                asset.patch = Some(patch);
+            }
+
+            let mut loaded = bevy::asset::LoadedAsset::new(asset);
+
+            if let Some(extends) = extends {
+                loaded = loaded.with_dependency(extends.as_str().into());
+            }
+
+            load_context.set_default_asset(loaded);
 
             Ok(())
         })
@@ -574,11 +1437,18 @@ impl Plugin for PlantPlugin {
         app_builder.add_asset::<Genome>();
         app_builder.add_asset::<PlantMaterial>();
         app_builder.add_asset_loader(GenomeLoader);
-        app_builder.add_system(plant_material_system.system());
+        app_builder.init_resource::<GenomeParents>();
+        app_builder.add_system(genome_inheritance_system.system());
+        app_builder.add_asset::<LSystem>();
+        app_builder.add_asset_loader(LSystemLoader);
+        app_builder.init_resource::<PlantMeshCache>();
+        app_builder.init_resource::<Season>();
+        app_builder.init_resource::<PlantOcclusion>();
+        app_builder.add_system(plant_mesh_cache_system.system());
 
         let asset_server = app_builder.world().get_resource::<AssetServer>().unwrap();
 
-        let vert = asset_server.load("shaders/plant.vert");
+        let vert = asset_server.load("shaders/plant.glsl.vert");
         let frag = asset_server.load("shaders/plant.frag");
 
         let pipeline = PipelineDescriptor {
@@ -592,7 +1462,7 @@ impl Plugin for PlantPlugin {
             })
         };
 
-        let vert = asset_server.load("shaders/plant_shadow.vert");
+        let vert = asset_server.load("shaders/plant_shadow.glsl.vert");
         let frag = asset_server.load("shaders/plant_shadow.frag");
 
         let shadow_pipeline = shadow_pipeline(ShaderStages {
@@ -634,3 +1504,229 @@ impl Plugin for PlantPlugin {
             .unwrap();
     }
 }
+
+#[cfg(test)]
+impl Genome {
+    /// The genome of `test.gno`, for tests to start from.
+    pub(crate) fn test() -> Self {
+        ron::from_str(include_str!("../assets/plants/test.gno")).unwrap()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn invalid(change: impl FnOnce(&mut Genome)) -> GenomeError {
+        let mut genome = Genome::test();
+
+        change(&mut genome);
+        genome.validate().unwrap_err()
+    }
+
+    fn at_least(field: &'static str, value: f64, min: f64) -> GenomeError {
+        GenomeError::OutOfRange {
+            field,
+            value,
+            min,
+            max: f64::INFINITY,
+        }
+    }
+
+    fn not_positive(field: &'static str, value: f64) -> GenomeError {
+        GenomeError::NotPositive { field, value }
+    }
+
+    #[test]
+    fn test_genome_is_valid() {
+        assert_eq!(Genome::test().validate(), Ok(()));
+    }
+
+    #[test]
+    fn validate_counts() {
+        assert_eq!(
+            invalid(|g| g.max_splits = 1),
+            at_least("max_splits", 1.0, 2.0)
+        );
+        assert_eq!(
+            invalid(|g| g.radial_segments = 2),
+            at_least("radial_segments", 2.0, 3.0)
+        );
+        assert_eq!(
+            invalid(|g| g.segments_per_branch = 0),
+            at_least("segments_per_branch", 0.0, 1.0)
+        );
+        assert_eq!(
+            invalid(|g| g.branches_per_split = std::ops::Range { start: 4, end: 2 }),
+            GenomeError::EmptyRange {
+                field: "branches_per_split",
+                start: 4,
+                end: 2,
+            }
+        );
+    }
+
+    #[test]
+    fn validate_non_negative_values() {
+        assert_eq!(
+            invalid(|g| g.starting_radius = -1.0),
+            at_least("starting_radius", -1.0, 0.0)
+        );
+        assert_eq!(
+            invalid(|g| g.leaf_size = -1.0),
+            at_least("leaf_size", -1.0, 0.0)
+        );
+        assert_eq!(
+            invalid(|g| g.leaf_density = Param::Constant(-1.0)),
+            at_least("leaf_density", -1.0, 0.0)
+        );
+        assert_eq!(
+            invalid(|g| g.branch_length = f32::NAN),
+            GenomeError::NotFinite {
+                field: "branch_length"
+            }
+        );
+    }
+
+    #[test]
+    fn validate_positive_values() {
+        assert_eq!(
+            invalid(|g| g.leaf_offset = 0.0),
+            not_positive("leaf_offset", 0.0)
+        );
+        assert_eq!(
+            invalid(|g| g.branch_bend = Param::Constant(0.0)),
+            not_positive("branch_bend", 0.0)
+        );
+        assert_eq!(
+            invalid(|g| g.branch_sway = Param::Constant(-1.0)),
+            not_positive("branch_sway", -1.0)
+        );
+    }
+
+    #[test]
+    fn validate_rejects_modes_that_conflict_with_colonization() {
+        let colonization = || {
+            ron::from_str(
+                "Some((
+                    envelope: Ellipsoid(center: (0.0, 5.0, 0.0), radii: (4.0, 3.0, 4.0)),
+                    attraction_points: 100,
+                    trunk_height: 2.0,
+                    step: 0.3,
+                    influence_radius: 1.5,
+                    kill_radius: 0.5,
+                    max_iterations: 50,
+                ))",
+            )
+            .unwrap()
+        };
+
+        assert_eq!(
+            invalid(|g| {
+                g.colonization = colonization();
+                g.envelope =
+                    ron::from_str("Some((shape: Column(base: 0.5, height: 9.0, radius: 0.9)))")
+                        .unwrap();
+            }),
+            GenomeError::Conflicting {
+                field: "envelope",
+                other: "colonization",
+            }
+        );
+        assert_eq!(
+            invalid(|g| {
+                g.colonization = colonization();
+                g.avoidance = ron::from_str("Some((cell: 0.25, strength: 2.0))").unwrap();
+            }),
+            GenomeError::Conflicting {
+                field: "avoidance",
+                other: "colonization",
+            }
+        );
+    }
+
+    #[test]
+    fn organ_errors_name_the_organ() {
+        let genome = Genome {
+            organs: ron::from_str(
+                "[
+                    (kind: Blossom, placement: Tips(chance: 0.5), shape: Card, size: 0.1),
+                    (kind: Fruit, placement: Tips(chance: 0.5), shape: Card, size: 0.0),
+                ]",
+            )
+            .unwrap(),
+            ..Genome::test()
+        };
+
+        let error = genome.validate().unwrap_err();
+
+        assert_eq!(
+            error,
+            GenomeError::Organ {
+                index: 1,
+                error: Box::new(GenomeError::NotPositive {
+                    field: "size",
+                    value: 0.0,
+                }),
+            }
+        );
+        assert_eq!(
+            error.to_string(),
+            "in `organs[1]`: `size` is 0, but must be greater than 0"
+        );
+    }
+
+    #[test]
+    fn instance_seed_prefers_explicit_then_pinned_then_derived() {
+        let unpinned = Genome::test();
+        let pinned = Genome {
+            seed: Some(7),
+            ..Genome::test()
+        };
+
+        assert_eq!(unpinned.instance_seed(None, PlantSeed(3)), 3);
+        assert_eq!(pinned.instance_seed(None, PlantSeed(3)), 7);
+        assert_eq!(pinned.instance_seed(Some(PlantSeed(5)), PlantSeed(3)), 5);
+        assert_eq!(unpinned.instance_seed(Some(PlantSeed(5)), PlantSeed(3)), 5);
+    }
+
+    #[test]
+    fn position_seeds_round_to_centimeters() {
+        let world = WorldSeed(42);
+        let seed = PlantSeed::from_position(world, Vec3::new(1.234, 0.0, -5.678));
+
+        assert_eq!(
+            seed,
+            PlantSeed::from_position(world, Vec3::new(1.2341, 9.0, -5.6779))
+        );
+        assert_ne!(
+            seed,
+            PlantSeed::from_position(world, Vec3::new(1.244, 0.0, -5.678))
+        );
+        assert_ne!(
+            seed,
+            PlantSeed::from_position(WorldSeed(43), Vec3::new(1.234, 0.0, -5.678))
+        );
+    }
+
+    #[test]
+    fn mix_spreads_nearby_values() {
+        assert_eq!(mix(1, 2), mix(1, 2));
+        assert_ne!(mix(1, 2), mix(2, 1));
+        assert!((mix(0, 1) ^ mix(0, 2)).count_ones() > 16);
+    }
+
+    #[test]
+    fn the_same_seed_grows_the_same_plant() {
+        let genome = Genome::test();
+        let (_, a) = genome.generate_mesh(1, None);
+        let (_, b) = genome.generate_mesh(1, None);
+        let (_, c) = genome.generate_mesh(2, None);
+
+        assert_eq!(
+            (a.vertices, a.leaves, a.branches),
+            (b.vertices, b.leaves, b.branches)
+        );
+        assert_ne!((a.vertices, a.leaves), (c.vertices, c.leaves));
+    }
+}
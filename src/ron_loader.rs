@@ -1,3 +1,5 @@
+/// Implements `AssetLoader` for assets read from RON files by extension. Every asset
+/// is checked with its `validate` method once it's read, and rejected if it fails.
 #[macro_export]
 macro_rules! ron_loader {
     ($loader:path, $($extension:expr => $asset:path),+) => {
@@ -12,12 +14,15 @@ macro_rules! ron_loader {
                     match load_context.path().extension().unwrap().to_str().unwrap() {
                         $(
                             $extension => {
+                                let error = |e: &dyn std::fmt::Display| {
+                                    anyhow::Error::msg(
+                                        format!("'{}': {}", load_context.path().to_string_lossy(), e)
+                                    )
+                                };
                                 let asset = ron::de::from_bytes::<$asset>(bytes)
-                                    .map_err(|e| {
-                                        anyhow::Error::msg(
-                                            format!("'{}': {}", load_context.path().to_string_lossy(), e)
-                                        )
-                                    })?;
+                                    .map_err(|e| error(&e))?;
+
+                                asset.validate().map_err(|e| error(&e))?;
                                 load_context.set_default_asset(bevy::asset::LoadedAsset::new(asset));
                             },
                         )+
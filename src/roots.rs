@@ -0,0 +1,448 @@
+use crate::plant::*;
+use crate::skeleton::*;
+use crate::tropism::Tropism;
+use bevy::prelude::*;
+use rand::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+/// Roots growing out of the base of the trunk, from surface roots and buttresses
+/// to a network splitting below the ground for uprooted plants and cut-aways.
+///
+/// Main roots leave the trunk `buttress` above the ground and run out along the
+/// surface, half buried, or dive below it at `dip`. Every split below that is only
+/// seen when the ground is taken away.
+///
+/// ```ron
+/// roots: Some((
+///     count: 5,
+///     length: 1.5,
+///     radius: 0.4,
+///     buttress: 0.4,
+///     flare: 0.5,
+/// )),
+/// roots: Some((
+///     count: 4,
+///     length: 1.2,
+///     radius: 0.5,
+///     dip: 0.3,
+///     splits: 3,
+///     gravitropism: 0.4,
+/// )),
+/// ```
+#[derive(Serialize, Deserialize, Clone, Debug)]
+pub struct Roots {
+    /// Number of main roots, spread evenly around the trunk.
+    pub count: usize,
+    /// Length of the main roots.
+    pub length: f32,
+    /// Radius of the main roots where they leave the trunk, relative to the trunk's.
+    pub radius: f32,
+    /// Height up the trunk the main roots leave from, tall roots stand out of the
+    /// trunk like the buttresses of rainforest trees.
+    #[serde(default)]
+    pub buttress: f32,
+    /// How much wider than its radius the trunk is at the ground, narrowing back
+    /// by the height of the buttresses.
+    #[serde(default)]
+    pub flare: f32,
+    /// Angle in radians below the surface the main roots dive at, less than a right
+    /// angle.
+    #[serde(default)]
+    pub dip: f32,
+    /// How many times roots split below the ground.
+    #[serde(default)]
+    pub splits: usize,
+    #[serde(default = "default_branches_per_split")]
+    pub branches_per_split: Range<usize>,
+    /// How much of its length and radius a root passes on to the roots splitting
+    /// off its end.
+    #[serde(default = "default_sustain")]
+    pub sustain: f32,
+    /// Angle in radians split roots turn away from the root they split off.
+    #[serde(default = "default_spread")]
+    pub spread: f32,
+    /// How strongly split roots turn downward per unit of length.
+    #[serde(default)]
+    pub gravitropism: f32,
+    #[serde(default = "default_segments")]
+    pub segments: usize,
+    #[serde(default = "default_radial_segments")]
+    pub radial_segments: usize,
+}
+
+fn default_branches_per_split() -> Range<usize> {
+    2..3
+}
+
+fn default_sustain() -> f32 {
+    0.6
+}
+
+fn default_spread() -> f32 {
+    0.6
+}
+
+fn default_segments() -> usize {
+    6
+}
+
+fn default_radial_segments() -> usize {
+    6
+}
+
+/// A root that's yet to be added to the skeleton, like [`Branch`] for the crown.
+struct Root {
+    split: usize,
+    parent: usize,
+    start: Vec3,
+    direction: Vec3,
+    length: f32,
+    start_radius: f32,
+    sway: f32,
+}
+
+impl Roots {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        check_count("roots.count", self.count, 1)?;
+        check_positive("roots.length", self.length)?;
+        check_positive("roots.radius", self.radius)?;
+        check_non_negative("roots.buttress", self.buttress)?;
+        check_non_negative("roots.flare", self.flare)?;
+        check_non_negative("roots.dip", self.dip)?;
+
+        // roots diving straight down would have to reach infinitely deep
+        if self.dip >= std::f32::consts::FRAC_PI_2 {
+            return Err(GenomeError::OutOfRange {
+                field: "roots.dip",
+                value: self.dip as f64,
+                min: 0.0,
+                max: std::f32::consts::FRAC_PI_2 as f64,
+            });
+        }
+
+        if self.branches_per_split.start > self.branches_per_split.end {
+            return Err(GenomeError::EmptyRange {
+                field: "roots.branches_per_split",
+                start: self.branches_per_split.start,
+                end: self.branches_per_split.end,
+            });
+        }
+
+        check_positive("roots.sustain", self.sustain)?;
+        check_non_negative("roots.spread", self.spread)?;
+
+        if !self.gravitropism.is_finite() {
+            return Err(GenomeError::NotFinite {
+                field: "roots.gravitropism",
+            });
+        }
+
+        check_count("roots.segments", self.segments, 1)?;
+        check_count("roots.radial_segments", self.radial_segments, 3)?;
+
+        Ok(())
+    }
+
+    /// Flares the base of the trunk and adds the roots to `skeleton`, which has to
+    /// hold the trunk as its first branch.
+    pub fn grow(&self, skeleton: &mut PlantSkeleton, rng: &mut rand::rngs::SmallRng) {
+        let trunk = match skeleton.branches.first() {
+            Some(trunk) => trunk.frames[0].radius,
+            None => return,
+        };
+
+        self.flare(&mut skeleton.branches[0]);
+
+        let mut roots = Vec::with_capacity(self.count);
+
+        for i in 0..self.count {
+            let spacing = std::f32::consts::TAU / self.count as f32;
+            let angle = i as f32 * spacing + rng.gen_range(-0.3..0.3) * spacing;
+
+            roots.push(self.main_root(skeleton, rng, angle, trunk * self.radius));
+        }
+
+        for split in 1..=self.splits {
+            for root in std::mem::take(&mut roots) {
+                let parent = &skeleton.branches[root];
+                let (end, length) = (parent.frames[parent.frames.len() - 1], parent.length);
+                let children =
+                    rng.gen_range(self.branches_per_split.start..=self.branches_per_split.end);
+
+                for _ in 0..children {
+                    let direction = self.turn(rng, end.rotation * Vec3::Y, self.spread);
+
+                    let child = Root {
+                        split,
+                        parent: root,
+                        start: end.position,
+                        direction,
+                        length: length * self.sustain,
+                        start_radius: end.radius,
+                        sway: end.sway,
+                    };
+
+                    roots.push(self.grow_root(skeleton, rng, child));
+                }
+            }
+        }
+    }
+
+    /// Widens the trunk toward the ground, over the height of the buttresses or
+    /// its first segment without any.
+    fn flare(&self, trunk: &mut SkeletonBranch) {
+        if self.flare == 0.0 {
+            return;
+        }
+
+        let height = if self.buttress > 0.0 {
+            self.buttress
+        } else {
+            trunk.frames.get(1).map_or(1.0, |frame| frame.position.y)
+        };
+
+        for frame in &mut trunk.frames {
+            let below = (1.0 - frame.position.y / height).clamp(0.0, 1.0);
+
+            frame.radius *= 1.0 + self.flare * below * below;
+        }
+
+        trunk.start_radius = trunk.frames[0].radius;
+    }
+
+    /// Adds a main root leaving the trunk toward `angle` around it, sloping down
+    /// from the buttress to the surface before it runs along or below it.
+    fn main_root(
+        &self,
+        skeleton: &mut PlantSkeleton,
+        rng: &mut rand::rngs::SmallRng,
+        mut angle: f32,
+        radius: f32,
+    ) -> usize {
+        let end_radius = if self.splits == 0 {
+            0.0
+        } else {
+            radius * self.sustain
+        };
+
+        let positions = (0..=self.segments)
+            .map(|segment| {
+                let t = segment as f32 / self.segments as f32;
+                let out = t * self.length;
+                let height = self.buttress * (1.0 - t).powi(3) - out * self.dip.tan();
+
+                // roots wind a little around stones and each other
+                angle += rng.gen_range(-0.1..0.1);
+
+                Vec3::new(angle.cos() * out, height, angle.sin() * out)
+            })
+            .collect::<Vec<_>>();
+
+        let mut frames = Vec::with_capacity(positions.len());
+        let mut sway = 0.0;
+
+        for (i, &position) in positions.iter().enumerate() {
+            let t = i as f32 / self.segments as f32;
+            let next = positions[(i + 1).min(self.segments)];
+            let prev = positions[i.saturating_sub(1)];
+
+            if i > 0 {
+                sway += position.distance(prev);
+            }
+
+            frames.push(SegmentFrame {
+                position,
+                rotation: Quat::from_rotation_arc(Vec3::Y, (next - prev).normalize()),
+                radius: radius + (end_radius - radius) * t,
+                sway,
+            });
+        }
+
+        self.add(skeleton, 0, 0, frames)
+    }
+
+    /// Adds a root splitting off below the ground, wandering and turning downward
+    /// as it grows.
+    fn grow_root(
+        &self,
+        skeleton: &mut PlantSkeleton,
+        rng: &mut rand::rngs::SmallRng,
+        root: Root,
+    ) -> usize {
+        let segment_length = root.length / self.segments as f32;
+        let end_radius = if root.split == self.splits {
+            0.0
+        } else {
+            root.start_radius * self.sustain
+        };
+
+        let mut position = root.start;
+        let mut direction = root.direction;
+        let mut frames = Vec::with_capacity(self.segments + 1);
+
+        for segment in 0..=self.segments {
+            let t = segment as f32 / self.segments as f32;
+
+            if segment > 0 {
+                direction = self.turn(rng, direction, 0.15);
+                direction = Tropism::turn(-Vec3::Y * self.gravitropism, direction, segment_length)
+                    * direction;
+                position += direction * segment_length;
+            }
+
+            frames.push(SegmentFrame {
+                position,
+                rotation: Quat::from_rotation_arc(Vec3::Y, direction),
+                radius: root.start_radius + (end_radius - root.start_radius) * t,
+                sway: root.sway + root.length * t,
+            });
+        }
+
+        let parent_frame = skeleton.branches[root.parent].frames.len() - 1;
+
+        self.add(skeleton, root.parent, parent_frame, frames)
+    }
+
+    /// `direction` turned by `angle` toward a random side.
+    fn turn(&self, rng: &mut rand::rngs::SmallRng, direction: Vec3, angle: f32) -> Vec3 {
+        let side = Quat::from_rotation_arc(Vec3::Y, direction)
+            * Quat::from_rotation_y(rng.gen_range(0.0..std::f32::consts::TAU))
+            * Vec3::X;
+
+        Quat::from_axis_angle(side, angle) * direction
+    }
+
+    fn add(
+        &self,
+        skeleton: &mut PlantSkeleton,
+        parent: usize,
+        parent_frame: usize,
+        frames: Vec<SegmentFrame>,
+    ) -> usize {
+        let first = frames[0];
+        let last = frames[frames.len() - 1];
+
+        skeleton.add_branch(SkeletonBranch {
+            parent: Some(parent),
+            parent_frame,
+            children: Vec::new(),
+            split: if parent == 0 {
+                1
+            } else {
+                skeleton.branches[parent].split + 1
+            },
+            start: first.position,
+            direction: first.rotation * Vec3::Y,
+            bend: Vec3::ZERO,
+            start_radius: first.radius,
+            end_radius: last.radius,
+            length: last.sway - first.sway,
+            radial_segments: self.radial_segments,
+            sway: first.sway,
+            frames,
+            leaves: Vec::new(),
+            organs: Vec::new(),
+            root: true,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn roots(source: &str) -> Roots {
+        ron::from_str(source).unwrap()
+    }
+
+    /// The skeleton of the test genome without roots, and with `roots`.
+    fn skeletons(roots: Roots) -> (PlantSkeleton, PlantSkeleton) {
+        let mut genome = Genome::test();
+        let plain = genome.generate_skeleton(1, Vec3::Y);
+
+        genome.roots = Some(roots);
+
+        (plain, genome.generate_skeleton(1, Vec3::Y))
+    }
+
+    #[test]
+    fn buttresses_run_down_to_the_surface() {
+        let (plain, skeleton) = skeletons(roots(
+            "(count: 4, length: 1.5, radius: 0.4, buttress: 0.4, flare: 0.5)",
+        ));
+        let trunk = (&plain.branches[0], &skeleton.branches[0]);
+        let roots = &skeleton.branches[plain.branches.len()..];
+
+        assert_eq!(roots.len(), 4);
+        assert!(plain.branches.iter().all(|branch| !branch.root));
+        assert_eq!(trunk.1.frames[0].radius, trunk.0.frames[0].radius * 1.5);
+
+        for root in roots {
+            let (first, last) = (root.frames[0], root.frames[root.frames.len() - 1]);
+
+            assert!(root.root);
+            assert_eq!(root.parent, Some(0));
+            assert_eq!(first.position.y, 0.4);
+            assert!(last.position.y.abs() < 0.0001);
+            assert!((Vec2::new(last.position.x, last.position.z).length() - 1.5).abs() < 0.0001);
+            assert_eq!(first.radius, trunk.0.frames[0].radius * 0.4);
+            assert_eq!(last.radius, 0.0);
+        }
+    }
+
+    #[test]
+    fn roots_split_below_the_ground() {
+        let (plain, skeleton) = skeletons(roots(
+            "(count: 3, length: 1.2, radius: 0.5, dip: 0.3, splits: 2, \
+             branches_per_split: (start: 2, end: 2), gravitropism: 0.4)",
+        ));
+        let roots = &skeleton.branches[plain.branches.len()..];
+        let depth = -1.2 * 0.3f32.tan();
+
+        assert_eq!(roots.len(), 3 + 6 + 12);
+
+        for root in roots {
+            let parent = &skeleton.branches[root.parent.unwrap()];
+            let first = root.frames[0];
+
+            assert!(root.root);
+
+            if parent.root {
+                let end = parent.frames[parent.frames.len() - 1];
+
+                assert_eq!(root.split, parent.split + 1);
+                assert_eq!(first.position, end.position);
+                assert_eq!(first.radius, end.radius);
+                assert!((root.length - parent.length * 0.6).abs() < 0.0001);
+                assert_eq!(root.end_radius == 0.0, root.split == 3);
+            } else {
+                assert!((root.frames[root.frames.len() - 1].position.y - depth).abs() < 0.0001);
+            }
+        }
+    }
+
+    #[test]
+    fn roots_may_not_dive_straight_down() {
+        let dip = std::f32::consts::FRAC_PI_2;
+
+        assert_eq!(
+            roots("(count: 3, length: 1.0, radius: 0.5, dip: 1.6)").validate(),
+            Err(GenomeError::OutOfRange {
+                field: "roots.dip",
+                value: 1.6f32 as f64,
+                min: 0.0,
+                max: dip as f64,
+            })
+        );
+        assert_eq!(
+            roots("(count: 3, length: 1.0, radius: 0.5, branches_per_split: (start: 3, end: 1))")
+                .validate(),
+            Err(GenomeError::EmptyRange {
+                field: "roots.branches_per_split",
+                start: 3,
+                end: 1,
+            })
+        );
+    }
+}
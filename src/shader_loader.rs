@@ -0,0 +1,228 @@
+use bevy::{
+    asset::{AssetLoader, AssetPath, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+    render::shader::{Shader, ShaderStage},
+    utils::{BoxedFuture, HashMap},
+};
+use std::path::{Path, PathBuf};
+
+/// A GLSL file meant to be `#include`d by shaders, loaded so it's watched for
+/// changes like any other asset.
+#[derive(TypeUuid)]
+#[uuid = "b3e7c9a2-58d1-4f6e-a0c4-2d9f71e86b35"]
+pub struct ShaderInclude(pub String);
+
+/// A shader as written, before its `#include`s are resolved, kept to resolve
+/// them again when one of the included files changes.
+#[derive(TypeUuid)]
+#[uuid = "6a1d4e8f-93b2-47c5-b8e0-f5c327a9d140"]
+pub struct ShaderTemplate {
+    pub stage: ShaderStage,
+    pub source: String,
+    /// The included files, relative to the asset folder.
+    pub includes: Vec<PathBuf>,
+}
+
+impl ShaderTemplate {
+    fn new(stage: ShaderStage, source: &str, dir: &Path) -> Self {
+        let includes = source
+            .lines()
+            .filter_map(include)
+            .map(|file| dir.join(file))
+            .collect();
+
+        Self {
+            stage,
+            source: source.to_owned(),
+            includes,
+        }
+    }
+
+    /// Replaces every `#include` line with the source of the file it names, or
+    /// `None` if one of them isn't known.
+    fn resolve(&self, included: &HashMap<PathBuf, String>) -> Option<Shader> {
+        let mut includes = self.includes.iter();
+        let mut source = String::new();
+
+        for line in self.source.lines() {
+            match include(line) {
+                Some(_) => source.push_str(included.get(includes.next()?)?),
+                None => source.push_str(line),
+            }
+
+            source.push('\n');
+        }
+
+        Some(Shader::from_glsl(self.stage, &source))
+    }
+}
+
+/// The file named by an `#include "file"` line.
+fn include(line: &str) -> Option<&str> {
+    line.trim()
+        .strip_prefix("#include")
+        .map(|file| file.trim().trim_matches('"'))
+}
+
+/// Loads `.glsl` files as [`ShaderInclude`]s.
+pub struct ShaderIncludeLoader;
+
+impl AssetLoader for ShaderIncludeLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let source = std::str::from_utf8(bytes)?.to_owned();
+
+            load_context.set_default_asset(LoadedAsset::new(ShaderInclude(source)));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["glsl"]
+    }
+}
+
+/// Loads `.glsl.vert` and `.glsl.frag` shaders like bevy's own loader, but first
+/// replaces every `#include "file"` line with the contents of `file`, relative to
+/// the shader. Included files aren't followed for includes of their own.
+///
+/// Other shaders are left to bevy's loader.
+pub struct ShaderLoader;
+
+impl AssetLoader for ShaderLoader {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let dir = load_context.path().parent().unwrap_or_else(|| "".as_ref());
+            let stage = match load_context.path().extension().unwrap().to_str().unwrap() {
+                "vert" => ShaderStage::Vertex,
+                "frag" => ShaderStage::Fragment,
+                e => unreachable!("{:?}", e),
+            };
+            let template = ShaderTemplate::new(stage, std::str::from_utf8(bytes)?, dir);
+
+            let mut included = HashMap::default();
+            for path in &template.includes {
+                let bytes = load_context.read_asset_bytes(path).await?;
+
+                included.insert(path.clone(), String::from_utf8(bytes)?);
+            }
+
+            let shader = template.resolve(&included).unwrap();
+            let shader = template
+                .includes
+                .iter()
+                .fold(LoadedAsset::new(shader), |shader, path| {
+                    shader.with_dependency(AssetPath::new_ref(path, None))
+                });
+
+            load_context.set_default_asset(shader);
+            load_context.set_labeled_asset("template", LoadedAsset::new(template));
+
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        &["glsl.vert", "glsl.frag"]
+    }
+}
+
+/// Resolves the includes of the shaders including a `.glsl` file again when it
+/// changes, which has bevy recompile the pipelines using them.
+pub fn shader_include_system(
+    asset_server: Res<AssetServer>,
+    mut events: EventReader<AssetEvent<ShaderInclude>>,
+    includes: Res<Assets<ShaderInclude>>,
+    templates: Res<Assets<ShaderTemplate>>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    for event in events.iter() {
+        let modified = match event {
+            AssetEvent::Modified { handle } => match asset_server.get_handle_path(handle) {
+                Some(path) => path.path().to_owned(),
+                None => continue,
+            },
+            AssetEvent::Created { .. } | AssetEvent::Removed { .. } => continue,
+        };
+
+        for (id, template) in templates.iter() {
+            if !template.includes.contains(&modified) {
+                continue;
+            }
+
+            let path = match asset_server.get_handle_path(id) {
+                Some(path) => path.path().to_owned(),
+                None => continue,
+            };
+            let included = template
+                .includes
+                .iter()
+                .filter_map(|path| {
+                    let include = includes.get(AssetPath::new_ref(path, None))?;
+
+                    Some((path.clone(), include.0.clone()))
+                })
+                .collect();
+
+            let shader = AssetPath::new_ref(&path, None);
+
+            match template.resolve(&included) {
+                Some(resolved) if shaders.contains(shader.clone()) => {
+                    *shaders.get_mut(shader).unwrap() = resolved;
+                }
+                Some(_) => (),
+                None => warn!("'{}': not every include is loaded", path.to_string_lossy()),
+            }
+        }
+    }
+}
+
+/// Loads the `.glsl.vert` and `.glsl.frag` shaders including `.glsl` files, and
+/// reloads them when an included file changes.
+pub struct ShaderIncludePlugin;
+
+impl Plugin for ShaderIncludePlugin {
+    fn build(&self, app_builder: &mut AppBuilder) {
+        app_builder.add_asset::<ShaderInclude>();
+        app_builder.add_asset::<ShaderTemplate>();
+        app_builder.add_asset_loader(ShaderIncludeLoader);
+        app_builder.add_asset_loader(ShaderLoader);
+        app_builder.add_system(shader_include_system.system());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::render::shader::ShaderSource;
+
+    #[test]
+    fn includes_are_resolved_relative_to_the_shader() {
+        let template = ShaderTemplate::new(
+            ShaderStage::Vertex,
+            "a\n  #include \"wind.glsl\"\nb",
+            "shaders".as_ref(),
+        );
+
+        assert_eq!(template.includes, [PathBuf::from("shaders/wind.glsl")]);
+        assert!(template.resolve(&HashMap::default()).is_none());
+
+        let mut included = HashMap::default();
+        included.insert(PathBuf::from("shaders/wind.glsl"), "w".to_owned());
+
+        match template.resolve(&included).unwrap().source {
+            ShaderSource::Glsl(source) => assert_eq!(source, "a\nw\nb\n"),
+            ShaderSource::Spirv(_) => unreachable!(),
+        }
+    }
+}
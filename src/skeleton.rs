@@ -0,0 +1,1270 @@
+use crate::metrics::PlantMetrics;
+use crate::occlusion::OcclusionBake;
+use crate::organ::Organ;
+use crate::plant::{check_positive, mix, GenomeError, Leaf, PlantContext, Ring};
+use bevy::{prelude::*, render::mesh::Indices};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+
+/// How fast a plant grows and when it stops.
+///
+/// A point on the plant is born once the tips have grown the path distance to it
+/// from the root, so every branch is born when its parent reaches the junction.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug)]
+pub struct Growth {
+    /// Distance a tip extends each second.
+    pub rate: f32,
+    /// Age in seconds at which the plant stops growing, every part of the plant
+    /// thickens until it reaches its full radius at this age.
+    pub max_age: f32,
+}
+
+impl Default for Growth {
+    fn default() -> Self {
+        Self {
+            rate: 0.5,
+            max_age: 30.0,
+        }
+    }
+}
+
+impl Growth {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        check_positive("growth.rate", self.rate)?;
+        check_positive("growth.max_age", self.max_age)?;
+
+        Ok(())
+    }
+
+    /// Age at which the point `distance` along the plant from the root is born.
+    pub fn birth(&self, distance: f32) -> f32 {
+        distance / self.rate
+    }
+
+    /// How much of its full radius the point `distance` from the root has at `age`.
+    pub fn thickness(&self, distance: f32, age: f32) -> f32 {
+        let birth = self.birth(distance);
+
+        if birth >= self.max_age {
+            return if age >= birth { 1.0 } else { 0.0 };
+        }
+
+        ((age - birth) / (self.max_age - birth)).clamp(0.0, 1.0)
+    }
+}
+
+/// Position, orientation and thickness of a branch at the end of one of its segments.
+#[derive(Clone, Copy, Debug)]
+pub struct SegmentFrame {
+    pub position: Vec3,
+    pub rotation: Quat,
+    pub radius: f32,
+    pub sway: f32,
+}
+
+impl SegmentFrame {
+    pub fn ring(&self, segments: usize) -> Ring {
+        let mut ring = Ring::generate(self.radius, segments, self.sway);
+
+        ring.rotate(self.rotation);
+        ring.translate(self.position);
+
+        ring
+    }
+
+    /// The frame `t` of the way from this frame to `other`.
+    pub fn lerp(&self, other: &SegmentFrame, t: f32) -> SegmentFrame {
+        SegmentFrame {
+            position: self.position.lerp(other.position, t),
+            rotation: self.rotation.lerp(other.rotation, t),
+            radius: self.radius + (other.radius - self.radius) * t,
+            sway: self.sway + (other.sway - self.sway) * t,
+        }
+    }
+}
+
+#[derive(Clone)]
+pub struct SkeletonBranch {
+    pub parent: Option<usize>,
+    /// Index of the frame in the parent branch this branch sprouts from.
+    pub parent_frame: usize,
+    pub children: Vec<usize>,
+    pub split: usize,
+    pub start: Vec3,
+    pub direction: Vec3,
+    pub bend: Vec3,
+    pub start_radius: f32,
+    pub end_radius: f32,
+    pub length: f32,
+    pub radial_segments: usize,
+    pub sway: f32,
+    /// The first frame is the start of the branch, every following frame ends a segment.
+    pub frames: Vec<SegmentFrame>,
+    pub leaves: Vec<Leaf>,
+    pub organs: Vec<Organ>,
+    /// Whether this is one of the [`Roots`], which stay still in the wind.
+    ///
+    /// [`Roots`]: crate::roots::Roots
+    pub root: bool,
+}
+
+impl SkeletonBranch {
+    /// The way the first segment heads. The first frame can face another way, as it
+    /// may be shared with the branch this one grows out of.
+    pub fn heading(&self) -> Vec3 {
+        (self.frames[1].position - self.frames[0].position)
+            .try_normalize()
+            .unwrap_or(self.frames[0].rotation * Vec3::Y)
+    }
+}
+
+/// The branching structure of a plant, independent of the triangles emitted for it.
+///
+/// Branches are stored parent first, so every branch can be processed after the
+/// branch it grows out of.
+#[derive(Clone, Default)]
+pub struct PlantSkeleton {
+    pub branches: Vec<SkeletonBranch>,
+}
+
+impl PlantSkeleton {
+    pub fn add_branch(&mut self, branch: SkeletonBranch) -> usize {
+        let index = self.branches.len();
+
+        if let Some(parent) = branch.parent {
+            assert!(parent < index, "parent branches must be added first");
+
+            self.branches[parent].children.push(index);
+        }
+
+        self.branches.push(branch);
+
+        index
+    }
+
+    /// A cheaper version of this skeleton for rendering at a distance, level 0 is the
+    /// skeleton itself.
+    ///
+    /// Every level halves ring and segment resolution, drops the deepest split and
+    /// merges pairs of leaves into one larger card. Organs are thinned out the same
+    /// way but keep their size.
+    pub fn lod(&self, level: usize) -> PlantSkeleton {
+        if level == 0 {
+            return self.clone();
+        }
+
+        let step = 1 << level;
+        let deepest = self.branches.iter().map(|b| b.split).max().unwrap_or(0);
+        let max_split = deepest.saturating_sub(level);
+
+        let mut skeleton = PlantSkeleton::default();
+        let mut remap: Vec<Option<usize>> = vec![None; self.branches.len()];
+        let mut orphaned_leaves = vec![Vec::new(); self.branches.len()];
+        let mut orphaned_organs = vec![Vec::new(); self.branches.len()];
+
+        for (index, branch) in self.branches.iter().enumerate() {
+            let parent = branch.parent.and_then(|parent| remap[parent]);
+
+            // dropped branches hand their leaves and organs to the closest ancestor that
+            // is kept
+            if branch.parent.is_some() && (parent.is_none() || branch.split > max_split) {
+                let kept = std::iter::successors(branch.parent, |&ancestor| {
+                    self.branches[ancestor].parent
+                })
+                .find(|&ancestor| remap[ancestor].is_some());
+
+                if let Some(ancestor) = kept {
+                    orphaned_leaves[ancestor].extend(branch.leaves.iter().cloned());
+                    orphaned_organs[ancestor].extend(branch.organs.iter().cloned());
+                }
+
+                continue;
+            }
+
+            let last = branch.frames.len() - 1;
+            let frames = branch
+                .frames
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| i % step == 0 || *i == last)
+                .map(|(_, frame)| *frame)
+                .collect::<Vec<_>>();
+
+            let parent_frame = match (branch.parent, parent) {
+                (Some(old_parent), Some(parent)) => {
+                    let parent_last = self.branches[old_parent].frames.len() - 1;
+
+                    if branch.parent_frame == parent_last {
+                        skeleton.branches[parent].frames.len() - 1
+                    } else {
+                        branch.parent_frame / step
+                    }
+                }
+                _ => 0,
+            };
+
+            remap[index] = Some(skeleton.add_branch(SkeletonBranch {
+                parent,
+                parent_frame,
+                children: Vec::new(),
+                radial_segments: reduce_segments(branch.radial_segments, level),
+                frames,
+                leaves: branch.leaves.clone(),
+                organs: branch.organs.clone(),
+                ..branch.clone()
+            }));
+        }
+
+        for (index, leaves) in orphaned_leaves.into_iter().enumerate() {
+            if let Some(new_index) = remap[index] {
+                skeleton.branches[new_index].leaves.extend(leaves);
+            }
+        }
+
+        for (index, organs) in orphaned_organs.into_iter().enumerate() {
+            if let Some(new_index) = remap[index] {
+                skeleton.branches[new_index].organs.extend(organs);
+            }
+        }
+
+        let scale = (step as f32).sqrt();
+
+        for branch in &mut skeleton.branches {
+            branch.leaves = branch
+                .leaves
+                .iter()
+                .step_by(step)
+                .map(|leaf| Leaf {
+                    size: leaf.size * scale,
+                    ..leaf.clone()
+                })
+                .collect();
+            branch.organs = branch.organs.iter().step_by(step).cloned().collect();
+        }
+
+        skeleton
+    }
+
+    /// This skeleton with only the organs that are out at `time` of the year.
+    pub fn in_season(&self, time: f32) -> PlantSkeleton {
+        let mut skeleton = self.clone();
+
+        for branch in &mut skeleton.branches {
+            branch.organs.retain(|organ| organ.in_season(time));
+        }
+
+        skeleton
+    }
+
+    /// This skeleton as it looks at `age`, branches are cut off where their tips have
+    /// grown to, and radii, leaves and organs are scaled by how long ago they were born.
+    pub fn grown(&self, age: f32, growth: &Growth) -> PlantSkeleton {
+        let age = age.min(growth.max_age);
+        let reach = age * growth.rate;
+
+        let mut skeleton = PlantSkeleton::default();
+        let mut remap: Vec<Option<usize>> = vec![None; self.branches.len()];
+
+        for (index, branch) in self.branches.iter().enumerate() {
+            let parent = branch.parent.and_then(|parent| remap[parent]);
+
+            if (branch.parent.is_some() && parent.is_none()) || branch.frames[0].sway >= reach {
+                continue;
+            }
+
+            let mut frames = Vec::with_capacity(branch.frames.len());
+
+            for frame in &branch.frames {
+                if frame.sway <= reach {
+                    frames.push(*frame);
+                    continue;
+                }
+
+                // the tip lies somewhere inside this segment
+                let prev = frames[frames.len() - 1];
+
+                if reach - prev.sway > 0.0001 {
+                    let t = (reach - prev.sway) / (frame.sway - prev.sway);
+
+                    frames.push(SegmentFrame {
+                        sway: reach,
+                        ..prev.lerp(frame, t)
+                    });
+                }
+
+                break;
+            }
+
+            if frames.len() < 2 {
+                continue;
+            }
+
+            for frame in &mut frames {
+                frame.radius *= growth.thickness(frame.sway, age);
+            }
+
+            let leaves = branch
+                .leaves
+                .iter()
+                .filter(|leaf| leaf.sway < reach)
+                .map(|leaf| Leaf {
+                    // leaves unfold as fast as the tips extend
+                    size: leaf.size.min((age - growth.birth(leaf.sway)) * growth.rate),
+                    ..leaf.clone()
+                })
+                .collect();
+
+            let organs = branch
+                .organs
+                .iter()
+                .filter(|organ| organ.sway < reach)
+                .map(|organ| Organ {
+                    size: organ
+                        .size
+                        .min((age - growth.birth(organ.sway)) * growth.rate),
+                    ..organ.clone()
+                })
+                .collect();
+
+            let first = frames[0];
+            let last = frames[frames.len() - 1];
+
+            remap[index] = Some(skeleton.add_branch(SkeletonBranch {
+                parent,
+                parent_frame: parent.map_or(0, |parent| {
+                    branch
+                        .parent_frame
+                        .min(skeleton.branches[parent].frames.len() - 1)
+                }),
+                children: Vec::new(),
+                start_radius: first.radius,
+                end_radius: last.radius,
+                length: last.sway - first.sway,
+                frames,
+                leaves,
+                organs,
+                ..branch.clone()
+            }));
+        }
+
+        skeleton
+    }
+
+    /// Meshes for every level of detail, along with the metrics of the full detail mesh
+    /// and the size of each level. Only the full detail mesh is baked with `occlusion`.
+    pub fn generate_lods(
+        &self,
+        levels: usize,
+        occlusion: Option<&OcclusionBake>,
+    ) -> (Vec<Mesh>, PlantMetrics) {
+        let (mesh, mut metrics) = self.generate_mesh(occlusion);
+
+        metrics.lod_triangles.push(metrics.triangles);
+        metrics.lod_vertices.push(metrics.vertices);
+
+        let meshes = std::iter::once(mesh)
+            .chain((1..levels).map(|level| {
+                let (mesh, lod) = self.lod(level).generate_mesh(None);
+
+                metrics.lod_triangles.push(lod.triangles);
+                metrics.lod_vertices.push(lod.vertices);
+
+                mesh
+            }))
+            .collect();
+
+        (meshes, metrics)
+    }
+
+    /// For every branch, the child that carries on its tube from its end, the one
+    /// heading on most nearly the way the branch ends. The others grow out of its side.
+    fn continuations(&self) -> Vec<Option<usize>> {
+        self.branches
+            .iter()
+            .map(|branch| {
+                let last = branch.frames.len() - 1;
+                let heading = branch.frames[last].rotation * Vec3::Y;
+                let alignment = |child: usize| heading.dot(self.branches[child].heading());
+
+                branch
+                    .children
+                    .iter()
+                    .copied()
+                    .filter(|&child| self.branches[child].parent_frame == last)
+                    .max_by(|&a, &b| {
+                        alignment(a)
+                            .partial_cmp(&alignment(b))
+                            .unwrap_or(std::cmp::Ordering::Equal)
+                    })
+            })
+            .collect()
+    }
+
+    /// The surface of `parent` around its frame `frame`. Past the end of the branch
+    /// that's the surface of `continuation`, which carries on its tube.
+    fn junction(&self, parent: usize, frame: usize, continuation: Option<usize>) -> Junction {
+        let branch = &self.branches[parent];
+        let at = branch.frames[frame];
+
+        let axis = match continuation.filter(|_| frame + 1 == branch.frames.len()) {
+            Some(child) => self.branches[child].heading(),
+            None => at.rotation * Vec3::Y,
+        };
+
+        Junction {
+            center: at.position,
+            axis,
+            radius: at.radius,
+            segments: branch.radial_segments,
+        }
+    }
+
+    /// The mesh of the skeleton, with `occlusion` baked into its vertex colors.
+    pub fn generate_mesh(&self, occlusion: Option<&OcclusionBake>) -> (Mesh, PlantMetrics) {
+        let mut vertices = Vec::new();
+        let mut indices = Vec::new();
+        let mut sway = Vec::new();
+        let mut color = Vec::new();
+        let mut uv = Vec::new();
+        let mut material = Vec::new();
+        let mut leaves = 0;
+
+        let mut ctx = PlantContext {
+            vertices: &mut vertices,
+            indices: &mut indices,
+            sway: &mut sway,
+            color: &mut color,
+            uv: &mut uv,
+            material: &mut material,
+            leaves: &mut leaves,
+        };
+
+        let mut wind = WindAttributes::default();
+        let mut end_loops: Vec<Vec<u32>> = Vec::with_capacity(self.branches.len());
+        let continuations = self.continuations();
+        let mut welds = Vec::new();
+        // the triangles of every segment of every branch's tube
+        let mut tubes: Vec<Vec<Range<usize>>> = Vec::with_capacity(self.branches.len());
+        let mut joints = Vec::new();
+
+        for (index, branch) in self.branches.iter().enumerate() {
+            for (i, leaf) in branch.leaves.iter().enumerate() {
+                leaf.generate_mesh(&mut ctx);
+
+                wind.fill(
+                    ctx.vertices.len(),
+                    leaf.pos,
+                    branch.split as f32 + 1.0,
+                    phase(index, i + 1),
+                );
+            }
+
+            for (i, organ) in branch.organs.iter().enumerate() {
+                organ.generate_mesh(&mut ctx);
+
+                wind.fill(
+                    ctx.vertices.len(),
+                    organ.pos,
+                    branch.split as f32 + 1.0,
+                    phase(index, branch.leaves.len() + i + 1),
+                );
+            }
+
+            let mut prev_loop = match branch.parent {
+                Some(parent) if continuations[parent] == Some(index) => end_loops[parent].clone(),
+                Some(parent) => {
+                    let junction =
+                        self.junction(parent, branch.parent_frame, continuations[parent]);
+                    let (ring, normals) = junction.collar(branch);
+                    let indices = ctx.add_ring(ring, branch.frames[0].sway);
+
+                    welds.extend(
+                        indices
+                            .iter()
+                            .zip(normals)
+                            .filter_map(|(&i, normal)| Some((i, normal?))),
+                    );
+                    joints.push((index, junction, indices.clone()));
+
+                    indices
+                }
+                None => {
+                    let start = &branch.frames[0];
+
+                    ctx.add_ring(start.ring(branch.radial_segments), start.sway)
+                }
+            };
+
+            let mut tube = Vec::with_capacity(branch.frames.len() - 1);
+
+            for frame in &branch.frames[1..] {
+                let mut indices = ctx.add_ring(frame.ring(branch.radial_segments), frame.sway);
+
+                let offset = align_loop(ctx.vertices, &prev_loop, &indices);
+                indices.rotate_left(offset);
+
+                let start = ctx.indices.len() / 3;
+                ctx.bridge_loops(&indices, &prev_loop);
+                tube.push(start..ctx.indices.len() / 3);
+
+                prev_loop = indices;
+            }
+
+            tubes.push(tube);
+
+            wind.fill(
+                ctx.vertices.len(),
+                branch.frames[0].position,
+                if branch.root {
+                    0.0
+                } else {
+                    branch.split as f32
+                },
+                phase(index, 0),
+            );
+
+            end_loops.push(prev_loop);
+        }
+
+        // open the parent's tube under every collar and stitch the hole to it, all
+        // tubes are done by now so a side branch can cut into its continuation
+        let mut removed = vec![false; indices.len() / 3];
+        let mut stitches = Vec::new();
+
+        for (index, junction, collar) in &joints {
+            let branch = &self.branches[*index];
+            let parent = branch.parent.unwrap();
+            let around = around_frame(&tubes, parent, branch.parent_frame, continuations[parent]);
+
+            if let Some((cut, stitch)) =
+                junction.cut(branch, collar, &vertices, &indices, &around, &removed)
+            {
+                for triangle in cut {
+                    removed[triangle] = true;
+                }
+
+                stitches.extend(stitch);
+            }
+        }
+
+        let indices = indices
+            .chunks_exact(3)
+            .zip(removed)
+            .filter(|(_, removed)| !removed)
+            .flat_map(|(triangle, _)| triangle.iter().copied())
+            .chain(stitches.into_iter().flatten())
+            .collect::<Vec<u32>>();
+
+        let mut mesh = Mesh::new(Default::default());
+        let mut normals = vec![Vec3::ZERO; vertices.len()];
+
+        for i in 0..indices.len() / 3 {
+            let i0 = indices[i * 3] as usize;
+            let i1 = indices[i * 3 + 1] as usize;
+            let i2 = indices[i * 3 + 2] as usize;
+
+            let v0 = vertices[i0];
+            let v1 = vertices[i1];
+            let v2 = vertices[i2];
+
+            let normal = (v1 - v0).cross(v2 - v0);
+
+            normals[i0] += normal;
+            normals[i1] += normal;
+            normals[i2] += normal;
+        }
+
+        // the poles of organ spheres repeat a vertex no triangle uses
+        for normal in &mut normals {
+            *normal = normal.normalize_or_zero();
+        }
+
+        // collars take the normal of the surface they sit on, so the shading runs on
+        // across the seam
+        for (i, normal) in welds {
+            normals[i as usize] = normal;
+        }
+
+        if let Some(occlusion) = occlusion {
+            // leaf and blossom cards are seen from both sides
+            let visibility = occlusion.bake(&vertices, &normals, &indices, |i| {
+                matches!(material[i], 1 | 2)
+            });
+
+            for (color, visibility) in color.iter_mut().zip(visibility) {
+                *color = Color::rgba(
+                    color.r() * visibility,
+                    color.g() * visibility,
+                    color.b() * visibility,
+                    color.a(),
+                );
+            }
+        }
+
+        let metrics = PlantMetrics::measure(self, &vertices, &indices, &material, leaves);
+
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_POSITION,
+            vertices
+                .into_iter()
+                .map(|v| v.into())
+                .collect::<Vec<[f32; 3]>>(),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_UV_0,
+            uv.into_iter().map(|v| v.into()).collect::<Vec<[f32; 2]>>(),
+        );
+        mesh.set_attribute(
+            Mesh::ATTRIBUTE_NORMAL,
+            normals
+                .into_iter()
+                .map(|v| v.into())
+                .collect::<Vec<[f32; 3]>>(),
+        );
+        mesh.set_attribute("Plant_Material", material);
+        mesh.set_attribute("Plant_Sway", sway);
+        mesh.set_attribute(
+            "Vertex_Color",
+            color
+                .into_iter()
+                .map(|v| v.into())
+                .collect::<Vec<[f32; 4]>>(),
+        );
+        mesh.set_attribute("Plant_Pivot", wind.pivot);
+        mesh.set_attribute("Plant_Level", wind.level);
+        mesh.set_attribute("Plant_Phase", wind.phase);
+        mesh.set_indices(Some(Indices::U32(indices)));
+
+        (mesh, metrics)
+    }
+}
+
+/// How much wider than its branch a collar may flare where it meets the parent.
+const COLLAR: f32 = 1.3;
+
+/// Where a branch grows out of the side of its parent, the parent's surface taken
+/// to be a cylinder of `radius` around `axis` through `center`.
+struct Junction {
+    center: Vec3,
+    axis: Vec3,
+    radius: f32,
+    /// Radial segments of the parent's tube.
+    segments: usize,
+}
+
+impl Junction {
+    /// The ring `branch` starts with, widened into a collar as far as the parent
+    /// allows and every vertex slid along the branch onto the parent's surface,
+    /// where `cut` opens the parent up to it. Comes with the surface normal of every
+    /// vertex that reached the surface within the first segment.
+    fn collar(&self, branch: &SkeletonBranch) -> (Ring, Vec<Option<Vec3>>) {
+        let start = branch.frames[0];
+        let heading = branch.heading();
+        let reach = start.position.distance(branch.frames[1].position) * 0.8;
+
+        let mut ring = SegmentFrame {
+            rotation: Quat::from_rotation_arc(Vec3::Y, heading),
+            radius: (start.radius * COLLAR).min(self.radius * 0.8),
+            ..start
+        }
+        .ring(branch.radial_segments);
+
+        // the heading and vertex offsets seen across the parent's axis
+        let across = heading - self.axis * heading.dot(self.axis);
+        let mut normals = Vec::with_capacity(ring.verts.len());
+
+        for v in &mut ring.verts {
+            let offset = *v - self.center;
+            let offset = offset - self.axis * offset.dot(self.axis);
+
+            // solves |offset + across * slide| = radius, sliding forward where the
+            // vertex starts inside the parent
+            let a = across.length_squared();
+            let b = 2.0 * offset.dot(across);
+            let c = offset.length_squared() - self.radius * self.radius;
+            let discriminant = b * b - 4.0 * a * c;
+
+            if a < 0.000_001 || discriminant < 0.0 {
+                normals.push(None);
+                continue;
+            }
+
+            let slide = (-b + discriminant.sqrt()) / (2.0 * a);
+
+            if slide.abs() > reach {
+                normals.push(None);
+                continue;
+            }
+
+            *v += heading * slide;
+            normals.push((offset + across * slide).try_normalize());
+        }
+
+        (ring, normals)
+    }
+
+    /// Opens the parent's tube under `collar`, the first ring of `branch`. Takes the
+    /// triangles of `around` that lie under the collar, widening the hole until it
+    /// takes the whole collar in, and returns them along with the triangles stitching
+    /// the edge of the hole to the collar. `None` when no hole fits the collar, such
+    /// as for branches running along their parent, which then sit on the surface.
+    fn cut(
+        &self,
+        branch: &SkeletonBranch,
+        collar: &[u32],
+        vertices: &[Vec3],
+        indices: &[u32],
+        around: &[usize],
+        removed: &[bool],
+    ) -> Option<(Vec<usize>, Vec<[u32; 3]>)> {
+        let start = branch.frames[0].position;
+        let heading = branch.heading();
+        let across = (heading - self.axis * heading.dot(self.axis)).try_normalize()?;
+
+        // hole and collar are compared looking down the branch
+        let u = (self.axis - heading * self.axis.dot(heading)).try_normalize()?;
+        let w = heading.cross(u);
+        let flat = |i: u32| {
+            let p = vertices[i as usize] - start;
+
+            Vec2::new(p.dot(u), p.dot(w))
+        };
+        let corners = |t: usize| [indices[t * 3], indices[t * 3 + 1], indices[t * 3 + 2]];
+
+        // how far around the parent from the branch a vertex is
+        let side = self.axis.cross(across);
+        let turn = |i: u32| {
+            let offset = vertices[i as usize] - self.center;
+
+            offset.dot(side).atan2(offset.dot(across)).abs()
+        };
+
+        let reach = collar.iter().map(|&i| flat(i).length()).fold(0.0, f32::max);
+        let half = (reach / self.radius).min(1.0).asin();
+        let step = std::f32::consts::TAU / self.segments as f32;
+
+        // the hole takes in whole columns of the tube as far around as the collar
+        // reaches, and more until it fits, but never past the side facing away
+        for columns in 1..=3 {
+            let limit = half + step * columns as f32 * 0.5;
+
+            if limit > std::f32::consts::FRAC_PI_2 + step * 0.5 {
+                break;
+            }
+
+            let cut = around
+                .iter()
+                .copied()
+                .filter(|&t| corners(t).iter().all(|&i| turn(i) <= limit))
+                .collect::<Vec<_>>();
+
+            // collars of branches at the same spot can't share a hole
+            if cut.iter().any(|&t| removed[t]) {
+                return None;
+            }
+
+            let stitch = hole_edge(&cut, &corners)
+                .and_then(|hole| self.stitch(&hole, collar, vertices, &flat));
+
+            if let Some(stitch) = stitch {
+                return Some((cut, stitch));
+            }
+        }
+
+        None
+    }
+
+    /// Triangles joining the edge of a hole in the parent to the collar inside it,
+    /// facing out of the parent. `None` unless, as seen through `flat`, the collar
+    /// lies inside the hole and no corner of the hole inside the collar.
+    fn stitch(
+        &self,
+        hole: &[u32],
+        collar: &[u32],
+        vertices: &[Vec3],
+        flat: &dyn Fn(u32) -> Vec2,
+    ) -> Option<Vec<[u32; 3]>> {
+        let hole_flat = hole.iter().map(|&i| flat(i)).collect::<Vec<_>>();
+        let mut collar = collar.to_vec();
+        let mut collar_flat = collar.iter().map(|&i| flat(i)).collect::<Vec<_>>();
+
+        if collar_flat.iter().any(|&p| !inside(&hole_flat, p))
+            || hole_flat.iter().any(|&p| inside(&collar_flat, p))
+        {
+            return None;
+        }
+
+        // both loops run the same way around
+        if (area(&hole_flat) > 0.0) != (area(&collar_flat) > 0.0) {
+            collar.reverse();
+            collar_flat.reverse();
+        }
+
+        // the hole starts where it's closest in direction to the first collar vertex,
+        // from there both loops are walked by the share of their length covered
+        let first = collar_flat[0].normalize_or_zero();
+        let start = (0..hole.len())
+            .max_by(|&a, &b| {
+                let a = hole_flat[a].normalize_or_zero().dot(first);
+                let b = hole_flat[b].normalize_or_zero().dot(first);
+
+                a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+            })
+            .unwrap_or(0);
+
+        let covered = |points: &[Vec2], from: usize| {
+            let mut length = 0.0;
+            let mut covered = vec![0.0];
+
+            for n in 0..points.len() {
+                let a = points[(from + n) % points.len()];
+                let b = points[(from + n + 1) % points.len()];
+
+                length += a.distance(b);
+                covered.push(length);
+            }
+
+            covered
+                .iter()
+                .map(|l| l / length.max(f32::EPSILON))
+                .collect::<Vec<_>>()
+        };
+        let hole_covered = covered(&hole_flat, start);
+        let collar_covered = covered(&collar_flat, 0);
+
+        let (mut h, mut c) = (0, 0);
+        let mut triangles = Vec::with_capacity(hole.len() + collar.len());
+
+        while h < hole.len() || c < collar.len() {
+            let a = hole[(start + h) % hole.len()];
+            let b = collar[c % collar.len()];
+
+            if c == collar.len() || (h < hole.len() && hole_covered[h + 1] <= collar_covered[c + 1])
+            {
+                triangles.push([a, b, hole[(start + h + 1) % hole.len()]]);
+                h += 1;
+            } else {
+                triangles.push([a, b, collar[(c + 1) % collar.len()]]);
+                c += 1;
+            }
+        }
+
+        // wind every triangle to face out of the parent
+        for triangle in &mut triangles {
+            let [a, b, c] = triangle.map(|i| vertices[i as usize]);
+            let out = (a + b + c) / 3.0 - self.center;
+            let out = out - self.axis * out.dot(self.axis);
+
+            if (b - a).cross(c - a).dot(out) < 0.0 {
+                triangle.swap(1, 2);
+            }
+        }
+
+        Some(triangles)
+    }
+}
+
+/// The triangles of the tubes right around frame `frame` of branch `parent`, on both
+/// sides of it. Past the end of the branch that's the first segment of the
+/// `continuation` carrying on its tube.
+fn around_frame(
+    tubes: &[Vec<Range<usize>>],
+    parent: usize,
+    frame: usize,
+    continuation: Option<usize>,
+) -> Vec<usize> {
+    let tube = &tubes[parent];
+    let before = frame.checked_sub(1).and_then(|segment| tube.get(segment));
+    let after = tube
+        .get(frame)
+        .or_else(|| continuation.and_then(|child| tubes[child].first()));
+
+    before.into_iter().chain(after).cloned().flatten().collect()
+}
+
+/// The vertices around the edge of the hole the triangles `cut` leave, in order.
+/// `None` unless the edge is a single loop.
+fn hole_edge(cut: &[usize], corners: &dyn Fn(usize) -> [u32; 3]) -> Option<Vec<u32>> {
+    let edges = cut
+        .iter()
+        .flat_map(|&t| {
+            let [a, b, c] = corners(t);
+
+            [(a, b), (b, c), (c, a)]
+        })
+        .collect::<HashSet<_>>();
+
+    // edges shared by two cut triangles run both ways, the rest are on the edge
+    let mut next = HashMap::new();
+
+    for &(a, b) in &edges {
+        if !edges.contains(&(b, a)) && next.insert(a, b).is_some() {
+            return None;
+        }
+    }
+
+    let first = *next.keys().next()?;
+    let mut hole = vec![first];
+
+    while let Some(&vertex) = next.get(hole.last()?) {
+        if vertex == first {
+            break;
+        }
+
+        if hole.len() == next.len() {
+            return None;
+        }
+
+        hole.push(vertex);
+    }
+
+    (hole.len() == next.len()).then_some(hole)
+}
+
+/// Whether `point` lies inside the polygon `loop_`.
+fn inside(loop_: &[Vec2], point: Vec2) -> bool {
+    let mut inside = false;
+
+    for n in 0..loop_.len() {
+        let a = loop_[n];
+        let b = loop_[(n + 1) % loop_.len()];
+
+        if (a.y > point.y) != (b.y > point.y)
+            && point.x < a.x + (point.y - a.y) / (b.y - a.y) * (b.x - a.x)
+        {
+            inside = !inside;
+        }
+    }
+
+    inside
+}
+
+/// Signed area of the polygon `loop_`, positive when it runs counterclockwise.
+fn area(loop_: &[Vec2]) -> f32 {
+    (0..loop_.len())
+        .map(|n| loop_[n].perp_dot(loop_[(n + 1) % loop_.len()]))
+        .sum::<f32>()
+        / 2.0
+}
+
+/// Per vertex data the plant shaders move vertices in the wind with, the point a
+/// branch or leaf pivots around, how deep in the hierarchy it is and the phase it
+/// swings with.
+#[derive(Default)]
+struct WindAttributes {
+    pivot: Vec<[f32; 3]>,
+    level: Vec<f32>,
+    phase: Vec<f32>,
+}
+
+impl WindAttributes {
+    /// Assigns the vertices up to `len` that have none yet.
+    fn fill(&mut self, len: usize, pivot: Vec3, level: f32, phase: f32) {
+        self.pivot.resize(len, pivot.into());
+        self.level.resize(len, level);
+        self.phase.resize(len, phase);
+    }
+}
+
+/// A phase over a full swing for `item` of branch `branch`, 0 being the branch itself
+/// and its leaves and organs after it. Hashed, so no two of them move in unison
+/// however many a branch carries.
+fn phase(branch: usize, item: usize) -> f32 {
+    let hash = mix(branch as u64, item as u64);
+
+    (hash >> 40) as f32 / (1u64 << 24) as f32 * std::f32::consts::TAU
+}
+
+/// Halves `segments` once per level, as long as the ring stays closed and the
+/// halved ring can still be bridged to its neighbours.
+fn reduce_segments(mut segments: usize, level: usize) -> usize {
+    for _ in 0..level {
+        if !segments.is_multiple_of(2) || segments / 2 < 3 {
+            break;
+        }
+
+        segments /= 2;
+    }
+
+    segments
+}
+
+/// Finds how far `next` has to be rotated so that its first vertex lines up with
+/// the first vertex of `prev`, which keeps the bridge between them from twisting.
+fn align_loop(vertices: &[Vec3], prev: &[u32], next: &[u32]) -> usize {
+    let center = |l: &[u32]| {
+        l.iter()
+            .fold(Vec3::ZERO, |sum, &i| sum + vertices[i as usize])
+            / l.len() as f32
+    };
+
+    let prev_center = center(prev);
+    let next_center = center(next);
+
+    let target = (vertices[prev[0] as usize] - prev_center).normalize_or_zero();
+
+    (0..next.len())
+        .max_by(|&a, &b| {
+            let a = (vertices[next[a] as usize] - next_center).dot(target);
+            let b = (vertices[next[b] as usize] - next_center).dot(target);
+
+            a.partial_cmp(&b).unwrap_or(std::cmp::Ordering::Equal)
+        })
+        .unwrap_or(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plant::{Genome, PlantGenerator};
+
+    fn skeleton() -> PlantSkeleton {
+        Genome::test().generate_skeleton(1, Vec3::Y)
+    }
+
+    #[test]
+    fn lod_drops_the_deepest_split() {
+        let skeleton = skeleton();
+        let deepest = |skeleton: &PlantSkeleton| skeleton.branches.iter().map(|b| b.split).max();
+        let lod = skeleton.lod(1);
+
+        assert_eq!(deepest(&lod), deepest(&skeleton).map(|split| split - 1));
+        assert!(lod.branches.len() < skeleton.branches.len());
+        assert!(lod.branches.iter().all(|branch| branch
+            .parent
+            .is_none_or(|parent| parent < lod.branches.len())));
+    }
+
+    #[test]
+    fn lod_reduces_segments_and_rings() {
+        let skeleton = skeleton();
+        let lod = skeleton.lod(1);
+        let trunk = (&skeleton.branches[0], &lod.branches[0]);
+
+        assert!(trunk.1.frames.len() < trunk.0.frames.len());
+        assert_eq!(trunk.1.radial_segments, trunk.0.radial_segments / 2);
+        assert_eq!(
+            trunk.1.frames.last().map(|f| f.position),
+            trunk.0.frames.last().map(|f| f.position)
+        );
+    }
+
+    #[test]
+    fn lod_keeps_the_leaves_of_dropped_branches() {
+        let skeleton = skeleton();
+        let deepest = skeleton.branches.iter().map(|b| b.split).max().unwrap_or(0);
+        let leaves = |split: &dyn Fn(usize) -> bool| -> Vec<usize> {
+            skeleton
+                .branches
+                .iter()
+                .filter(|b| split(b.split))
+                .map(|b| b.leaves.len())
+                .collect()
+        };
+
+        // more than every other leaf of the kept branches alone
+        let kept = leaves(&|split| split < deepest)
+            .iter()
+            .map(|&n| n.div_ceil(2))
+            .sum::<usize>();
+        let lod = skeleton.lod(1);
+
+        assert!(leaves(&|split| split == deepest).iter().sum::<usize>() > 0);
+        assert!(lod.branches.iter().map(|b| b.leaves.len()).sum::<usize>() > kept);
+    }
+
+    #[test]
+    fn every_level_of_detail_is_smaller() {
+        let (meshes, metrics) = skeleton().generate_lods(3, None);
+
+        assert_eq!(meshes.len(), 3);
+        assert_eq!(metrics.lod_triangles[0], metrics.triangles);
+        assert!(metrics
+            .lod_triangles
+            .windows(2)
+            .all(|pair| pair[1] < pair[0]));
+        assert!(metrics
+            .lod_vertices
+            .windows(2)
+            .all(|pair| pair[1] < pair[0]));
+    }
+
+    /// A straight vertical trunk of unit radius, with a thin branch heading along +X
+    /// out of its middle if `side` is set.
+    fn junction_skeleton(side: bool) -> PlantSkeleton {
+        let branch =
+            |parent, parent_frame, radial_segments, frames: Vec<SegmentFrame>| SkeletonBranch {
+                parent,
+                parent_frame,
+                children: Vec::new(),
+                split: parent.map_or(0, |_| 1),
+                start: frames[0].position,
+                direction: frames[0].rotation * Vec3::Y,
+                bend: Vec3::ZERO,
+                start_radius: frames[0].radius,
+                end_radius: frames[0].radius,
+                length: frames[0]
+                    .position
+                    .distance(frames[frames.len() - 1].position),
+                radial_segments,
+                sway: 0.0,
+                frames,
+                leaves: Vec::new(),
+                organs: Vec::new(),
+                root: false,
+            };
+        let frame = |position: Vec3, rotation: Quat, radius: f32| SegmentFrame {
+            position,
+            rotation,
+            radius,
+            sway: 0.0,
+        };
+
+        let mut skeleton = PlantSkeleton::default();
+        let trunk = (0..5)
+            .map(|y| frame(Vec3::Y * y as f32, Quat::IDENTITY, 1.0))
+            .collect();
+
+        skeleton.add_branch(branch(None, 0, 16, trunk));
+
+        if side {
+            let rotation = Quat::from_rotation_arc(Vec3::Y, Vec3::X);
+            let side = [0.0, 1.5, 2.5, 3.5]
+                .iter()
+                .map(|&x| frame(Vec3::new(x, 2.0, 0.0), rotation, 0.3))
+                .collect();
+
+            skeleton.add_branch(branch(Some(0), 2, 8, side));
+        }
+
+        skeleton
+    }
+
+    fn positions_and_indices(mesh: &Mesh) -> (Vec<Vec3>, Vec<u32>) {
+        let positions = match mesh.attribute(Mesh::ATTRIBUTE_POSITION) {
+            Some(bevy::render::mesh::VertexAttributeValues::Float3(positions)) => {
+                positions.iter().map(|&p| Vec3::from(p)).collect()
+            }
+            _ => panic!("mesh has no positions"),
+        };
+        let indices = match mesh.indices() {
+            Some(Indices::U32(indices)) => indices.clone(),
+            _ => panic!("mesh has no indices"),
+        };
+
+        (positions, indices)
+    }
+
+    /// Edges used by only one triangle.
+    fn open_edges(indices: &[u32]) -> usize {
+        let mut edges = HashMap::new();
+
+        for triangle in indices.chunks_exact(3) {
+            for n in 0..3 {
+                let (a, b) = (triangle[n], triangle[(n + 1) % 3]);
+
+                *edges.entry((a.min(b), a.max(b))).or_insert(0) += 1;
+            }
+        }
+
+        edges.values().filter(|&&count| count == 1).count()
+    }
+
+    #[test]
+    fn junction_cuts_the_parent_open_under_the_collar() {
+        let (positions, indices) =
+            positions_and_indices(&junction_skeleton(true).generate_mesh(None).0);
+
+        // the trunk's ring vertices facing the branch at the height it grows out at
+        let under = positions
+            .iter()
+            .enumerate()
+            .filter(|(_, p)| (p.y - 2.0).abs() < 0.001 && p.x > 0.9 && p.z.abs() < 0.3)
+            .map(|(i, _)| i as u32)
+            .collect::<Vec<_>>();
+
+        assert!(!under.is_empty());
+        assert!(indices.iter().all(|i| !under.contains(i)));
+    }
+
+    #[test]
+    fn junction_is_stitched_shut() {
+        let trunk = junction_skeleton(false).generate_mesh(None).0;
+        let plant = junction_skeleton(true).generate_mesh(None).0;
+
+        // the only new open edges are around the tip of the branch
+        assert_eq!(
+            open_edges(&positions_and_indices(&plant).1),
+            open_edges(&positions_and_indices(&trunk).1) + 8
+        );
+    }
+
+    #[test]
+    fn reduce_segments_keeps_rings_closed() {
+        assert_eq!(reduce_segments(16, 2), 4);
+        assert_eq!(reduce_segments(12, 3), 3);
+        assert_eq!(reduce_segments(7, 1), 7);
+        assert_eq!(reduce_segments(6, 3), 3);
+    }
+
+    #[test]
+    fn thickness_grows_from_birth_to_max_age() {
+        let growth = Growth {
+            rate: 2.0,
+            max_age: 10.0,
+        };
+
+        assert_eq!(growth.birth(4.0), 2.0);
+        assert_eq!(growth.thickness(4.0, 1.0), 0.0);
+        assert_eq!(growth.thickness(4.0, 6.0), 0.5);
+        assert_eq!(growth.thickness(4.0, 12.0), 1.0);
+        // born as the plant stops growing
+        assert_eq!(growth.thickness(20.0, 10.0), 1.0);
+    }
+
+    #[test]
+    fn grown_plants_are_whole() {
+        let skeleton = skeleton();
+        let growth = Growth {
+            rate: 1000.0,
+            max_age: 1.0,
+        };
+        let grown = skeleton.grown(5.0, &growth);
+
+        assert_eq!(grown.branches.len(), skeleton.branches.len());
+        for (grown, branch) in grown.branches.iter().zip(&skeleton.branches) {
+            assert_eq!(grown.frames.len(), branch.frames.len());
+            assert_eq!(grown.leaves.len(), branch.leaves.len());
+            assert_eq!(grown.frames[0].radius, branch.frames[0].radius);
+        }
+    }
+
+    #[test]
+    fn young_plants_are_cut_off_where_their_tips_reach() {
+        let skeleton = skeleton();
+        let growth = Growth {
+            rate: 0.5,
+            max_age: 30.0,
+        };
+        let age = 1.9;
+        let grown = skeleton.grown(age, &growth);
+        let reach = age * growth.rate;
+        let trunk = (&skeleton.branches[0], &grown.branches[0]);
+
+        assert!(grown.branches.len() < skeleton.branches.len());
+        assert!(trunk.1.frames.len() < trunk.0.frames.len());
+        assert!((trunk.1.frames.last().unwrap().sway - reach).abs() < 0.0001);
+        assert!(trunk.1.frames[0].radius < trunk.0.frames[0].radius);
+
+        for branch in &grown.branches {
+            assert!(branch.frames.iter().all(|frame| frame.sway <= reach));
+            assert!(branch.leaves.iter().all(|leaf| {
+                leaf.sway < reach && leaf.size <= (age - growth.birth(leaf.sway)) * growth.rate
+            }));
+            assert!(branch
+                .parent
+                .is_none_or(|parent| branch.parent_frame < grown.branches[parent].frames.len()));
+        }
+    }
+}
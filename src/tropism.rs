@@ -0,0 +1,64 @@
+use crate::inheritance::present;
+use crate::plant::*;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+/// How growth turns toward the light and with or against gravity, applied to every
+/// segment as the branch is built.
+///
+/// ```ron
+/// tropism: ( gravitropism: -0.4 ),
+/// tropism: ( phototropism: 0.2, light: (1.0, 1.0, 0.0) ),
+/// ```
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, Default, PartialEq)]
+#[serde(default)]
+pub struct Tropism {
+    /// How strongly growth turns toward the light, per unit of length.
+    pub phototropism: f32,
+    /// How strongly growth turns upward per unit of length, negative for weeping forms.
+    pub gravitropism: f32,
+    /// Direction toward the light in the plant's own space, the direction of the sun
+    /// where the plant is planted if left out.
+    #[serde(deserialize_with = "present")]
+    pub light: Option<Vec3>,
+}
+
+impl Tropism {
+    pub fn validate(&self) -> Result<(), GenomeError> {
+        if !self.phototropism.is_finite() {
+            return Err(GenomeError::NotFinite {
+                field: "tropism.phototropism",
+            });
+        }
+
+        if !self.gravitropism.is_finite() {
+            return Err(GenomeError::NotFinite {
+                field: "tropism.gravitropism",
+            });
+        }
+
+        if let Some(light) = self.light {
+            check_positive("tropism.light", light.length())?;
+        }
+
+        Ok(())
+    }
+
+    /// The direction growth is pulled toward, scaled by how strongly per unit of
+    /// length. `light` is used unless the tropism sets its own.
+    pub fn pull(&self, light: Vec3) -> Vec3 {
+        self.light.unwrap_or(light).normalize_or_zero() * self.phototropism
+            + Vec3::Y * self.gravitropism
+    }
+
+    /// The rotation turning `direction` toward `pull` over `length` of growth.
+    pub fn turn(pull: Vec3, direction: Vec3, length: f32) -> Quat {
+        let target = (direction + pull * length).normalize_or_zero();
+
+        if target == Vec3::ZERO {
+            Quat::IDENTITY
+        } else {
+            Quat::from_rotation_arc(direction, target)
+        }
+    }
+}
@@ -0,0 +1,169 @@
+use bevy::{
+    core::AsBytes,
+    ecs::system::BoxedSystem,
+    prelude::*,
+    render::{
+        camera::ActiveCameras,
+        render_graph::{base, CommandQueue, Node, RenderGraph, ResourceSlots, SystemNode},
+        renderer::{
+            BufferId, BufferInfo, BufferMapMode, BufferUsage, RenderContext, RenderResourceBinding,
+            RenderResourceBindings, RenderResourceContext,
+        },
+    },
+};
+
+pub const WIND_NODE: &str = "wind_node";
+
+/// The wind blowing through every plant, uploaded to the plant shaders as the
+/// `Wind` uniform.
+#[derive(Clone, Debug)]
+pub struct Wind {
+    /// Direction the wind blows towards, on the ground plane.
+    pub direction: Vec2,
+    pub strength: f32,
+    /// How much stronger than `strength` the wind blows in a gust.
+    pub gust: f32,
+    /// Distance between gust fronts rolling through the world.
+    pub gust_scale: f32,
+    /// How restless branches and leaves are, independent of the main wind.
+    pub turbulence: f32,
+}
+
+impl Default for Wind {
+    fn default() -> Self {
+        Self {
+            direction: Vec2::new(1.0, 0.3),
+            strength: 0.4,
+            gust: 0.6,
+            gust_scale: 30.0,
+            turbulence: 0.5,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct WindNode {
+    command_queue: CommandQueue,
+}
+
+impl Node for WindNode {
+    fn update(
+        &mut self,
+        _world: &World,
+        render_context: &mut dyn RenderContext,
+        _input: &ResourceSlots,
+        _output: &mut ResourceSlots,
+    ) {
+        self.command_queue.execute(render_context);
+    }
+}
+
+impl SystemNode for WindNode {
+    fn get_system(&self) -> BoxedSystem {
+        let system = wind_node_system.system().config(|config| {
+            config.0 = Some(WindNodeState {
+                command_queue: self.command_queue.clone(),
+                wind_buffer: None,
+                staging_buffer: None,
+            })
+        });
+        Box::new(system)
+    }
+}
+
+#[derive(Default)]
+pub struct WindNodeState {
+    command_queue: CommandQueue,
+    wind_buffer: Option<BufferId>,
+    staging_buffer: Option<BufferId>,
+}
+
+pub fn wind_node_system(
+    mut state: Local<WindNodeState>,
+    render_resource_context: Res<Box<dyn RenderResourceContext>>,
+    mut render_resource_bindings: ResMut<RenderResourceBindings>,
+    mut active_cameras: ResMut<ActiveCameras>,
+    time: Res<Time>,
+    wind: Res<Wind>,
+) {
+    let direction = wind.direction.normalize_or_zero();
+
+    // matches the std140 layout of the `Wind` block
+    let data = [
+        direction.x,
+        direction.y,
+        wind.strength,
+        time.seconds_since_startup() as f32,
+        wind.gust,
+        wind.gust_scale,
+        wind.turbulence,
+        0.0,
+    ];
+    let size = std::mem::size_of_val(&data);
+
+    let staging_buffer = if let Some(staging_buffer) = state.staging_buffer {
+        render_resource_context.map_buffer(staging_buffer, BufferMapMode::Write);
+
+        staging_buffer
+    } else {
+        let buffer = render_resource_context.create_buffer(BufferInfo {
+            size,
+            buffer_usage: BufferUsage::UNIFORM | BufferUsage::COPY_SRC | BufferUsage::COPY_DST,
+            ..Default::default()
+        });
+        let binding = RenderResourceBinding::Buffer {
+            buffer,
+            range: 0..size as u64,
+            dynamic_index: None,
+        };
+
+        render_resource_bindings.set("Wind", binding.clone());
+
+        if let Some(active_camera) = active_cameras.get_mut(base::camera::CAMERA_3D) {
+            active_camera.bindings.set("Wind", binding);
+        }
+
+        state.wind_buffer = Some(buffer);
+
+        let staging_buffer = render_resource_context.create_buffer(BufferInfo {
+            size,
+            buffer_usage: BufferUsage::COPY_SRC | BufferUsage::MAP_WRITE,
+            mapped_at_creation: true,
+        });
+
+        state.staging_buffer = Some(staging_buffer);
+
+        staging_buffer
+    };
+
+    render_resource_context.write_mapped_buffer(staging_buffer, 0..size as u64, &mut |bytes, _| {
+        bytes[0..size].copy_from_slice(data.as_bytes());
+    });
+
+    render_resource_context.unmap_buffer(staging_buffer);
+    let wind_buffer = state.wind_buffer.unwrap();
+    state
+        .command_queue
+        .copy_buffer_to_buffer(staging_buffer, 0, wind_buffer, 0, size as u64);
+}
+
+pub struct WindPlugin;
+
+impl Plugin for WindPlugin {
+    fn build(&self, app_builder: &mut AppBuilder) {
+        app_builder.init_resource::<Wind>();
+
+        let mut render_graph = app_builder
+            .world_mut()
+            .get_resource_mut::<RenderGraph>()
+            .unwrap();
+
+        render_graph.add_system_node(WIND_NODE, WindNode::default());
+        render_graph
+            .add_node_edge(WIND_NODE, base::node::MAIN_PASS)
+            .unwrap();
+        render_graph
+            .add_node_edge(WIND_NODE, crate::sun::SHADOWS_NODE)
+            .unwrap();
+    }
+}